@@ -1,6 +1,7 @@
 use criterion::*;
 use dhtsearch::api::*;
 use dhtsearch::file_types;
+use dhtsearch::filerow::{info_files_to_file_rows, FileRowTree};
 
 fn bench_file_types(c: &mut Criterion) {
     fallible_bench_file_types(c).unwrap()
@@ -18,5 +19,48 @@ fn fallible_bench_file_types(c: &mut Criterion) -> anyhow::Result<()> {
     Ok(())
 }
 
-criterion_group!(benches, bench_file_types);
+// Something like a big archive torrent: nested directories and lots of numbered files.
+fn synthetic_files(count: usize) -> Vec<UpvertedFile> {
+    (0..count)
+        .map(|index| UpvertedFile {
+            path: vec![
+                format!("volume {}", index / 10_000).into(),
+                format!("disc {}", index / 100 % 100).into(),
+                format!("track {}.flac", index).into(),
+            ],
+            length: index as FileLength,
+        })
+        .collect()
+}
+
+fn bench_file_tree(c: &mut Criterion) {
+    fallible_bench_file_tree(c).unwrap()
+}
+
+fn fallible_bench_file_tree(c: &mut Criterion) -> anyhow::Result<()> {
+    let json_payload_str = include_str!("3670d38c31d660d690384731483e145695586797.infoFiles.json");
+    let payload: InfoFilesPayload = serde_json::from_str(json_payload_str)?;
+    let mut group = c.benchmark_group("file_tree");
+    for (name, upverted) in [
+        ("3670d38c", payload[0].upverted_files()),
+        ("synthetic_100k", synthetic_files(100_000)),
+    ] {
+        group.bench_function(format!("{}/file_rows", name), |b| {
+            b.iter(|| black_box(info_files_to_file_rows(&upverted)))
+        });
+        let file_rows = info_files_to_file_rows(&upverted);
+        let tree = FileRowTree::new(&file_rows);
+        assert_eq!(
+            tree.node(FileRowTree::ROOT).size,
+            upverted.iter().map(|file| file.length as u64).sum::<u64>()
+        );
+        group.bench_function(format!("{}/build", name), |b| {
+            b.iter(|| black_box(FileRowTree::new(&file_rows)))
+        });
+    }
+    group.finish();
+    Ok(())
+}
+
+criterion_group!(benches, bench_file_types, bench_file_tree);
 criterion_main!(benches);
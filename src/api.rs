@@ -1,14 +1,62 @@
 use super::*;
-use gloo_net::http::Request;
-use gloo_net::http::Response;
+use async_trait::async_trait;
 use log::info;
 use serde::de::DeserializeOwned;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize, Serializer};
 use std::fmt::Debug;
 
+mod charset;
+mod compression;
+mod endpoint;
+mod file_path;
+mod file_tree;
+mod info_hash;
 mod info_name;
+mod retry;
+mod timestamp;
+mod transport;
 
-use info_name::InfoName;
+pub use charset::DecodedBytes;
+pub use compression::{Compression, COMPRESSION_QUERY_PARAM};
+pub(crate) use endpoint::local_storage;
+pub use endpoint::{EndpointPreset, IndexerEndpoint, ENDPOINT_QUERY_PARAM};
+pub use file_tree::{FileTree, FileTreeFile, FileTreeNode};
+pub use info_hash::{InfoHash, TorrentVersion};
+pub use info_name::InfoName;
+pub use retry::RetryPolicy;
+pub use timestamp::Timestamp;
+#[cfg(not(target_arch = "wasm32"))]
+pub use transport::ReqwestTransport;
+pub use transport::{
+    CancelToken, DefaultTransport, GlooTransport, HttpRequest, HttpResponse, Method, Transport,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchPage {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl SearchPage {
+    pub const DEFAULT_LIMIT: usize = 50;
+
+    pub fn first() -> Self {
+        Self::at_offset(0)
+    }
+
+    pub fn at_offset(offset: usize) -> Self {
+        Self {
+            offset,
+            limit: Self::DEFAULT_LIMIT,
+        }
+    }
+}
+
+impl Default for SearchPage {
+    fn default() -> Self {
+        Self::first()
+    }
+}
 
 #[derive(Clone, PartialEq, Deserialize, Default)]
 #[serde(rename_all = "PascalCase")]
@@ -21,15 +69,29 @@ pub struct InfosSearch {
 #[derive(Clone, PartialEq, Deserialize, Default)]
 #[serde(rename_all = "PascalCase")]
 pub struct InfoItem {
-    pub info_hash: String,
+    // The v2 hash for v2 only torrents.
+    pub info_hash: InfoHash,
+    // Set for hybrid torrents.
+    #[serde(default)]
+    pub info_hash_v2: Option<InfoHash>,
     pub name: String,
     pub swarm_info: SwarmInfo,
     pub size: u64,
-    pub age: String,
+    pub age: Timestamp,
     pub no_swarm_info: bool,
 }
 
-#[derive(Clone, PartialEq, Deserialize, Default, Debug)]
+impl InfoItem {
+    pub fn version(&self) -> TorrentVersion {
+        TorrentVersion::new(&self.info_hash, self.info_hash_v2.as_ref())
+    }
+
+    pub fn info_hashes(&self) -> impl Iterator<Item = InfoHash> {
+        std::iter::once(self.info_hash).chain(self.info_hash_v2)
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Default, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct SwarmInfo {
     pub seeders: u32,
@@ -51,26 +113,86 @@ impl Display for SwarmInfo {
 }
 
 #[derive(Clone, PartialEq, Deserialize, Default, Debug)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", from = "RawInfoFiles")]
 pub struct InfoFiles {
     pub info: Info,
+    // Flattened from file_tree for v2 only torrents, see InfoFiles::new.
     pub files: Vec<File>,
+    #[serde(default)]
+    pub file_tree: Option<FileTree>,
+}
+
+// As the indexer sends it, the files being empty for v2 only torrents.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawInfoFiles {
+    info: Info,
+    files: Vec<File>,
+    #[serde(default)]
+    file_tree: Option<FileTree>,
+}
+
+impl From<RawInfoFiles> for InfoFiles {
+    fn from(raw: RawInfoFiles) -> Self {
+        Self::new(raw.info, raw.files, raw.file_tree)
+    }
+}
+
+// Back in the indexer's shape, so v2 only torrents don't store their files twice.
+impl Serialize for InfoFiles {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct Raw<'a> {
+            info: &'a Info,
+            files: &'a [File],
+            file_tree: &'a Option<FileTree>,
+        }
+        let flattened = self.file_tree.is_some() && self.info.version() == TorrentVersion::V2;
+        Raw {
+            info: &self.info,
+            files: if flattened { &[] } else { &self.files },
+            file_tree: &self.file_tree,
+        }
+        .serialize(serializer)
+    }
 }
 
 pub struct UpvertedFile {
-    pub path: Vec<String>,
+    pub path: Vec<DecodedBytes>,
     pub length: FileLength,
 }
 
 impl InfoFiles {
+    // The files are wanted all over the place, so v2 only torrents have theirs flattened from the
+    // file tree once here. Hybrid torrents have both, and the v1 list is kept so file indexes line
+    // up with what v1 clients use.
+    pub fn new(info: Info, mut files: Vec<File>, file_tree: Option<FileTree>) -> Self {
+        if let Some(file_tree) = file_tree.as_ref().filter(|_| files.is_empty()) {
+            files = file_tree.files();
+        }
+        Self {
+            info,
+            files,
+            file_tree,
+        }
+    }
+
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+
     pub fn upverted_files(&self) -> Vec<UpvertedFile> {
-        self.files
+        self.files()
             .iter()
             .map(|file| UpvertedFile {
                 path: file
                     .path
                     .clone()
-                    .unwrap_or_else(|| vec![self.info.name.to_string()]),
+                    .unwrap_or_else(|| vec![(*self.info.name).clone()]),
                 length: file.length,
             })
             .collect()
@@ -79,117 +201,253 @@ impl InfoFiles {
 
 pub type InfoFilesPayload = Vec<InfoFiles>;
 
-#[derive(Clone, PartialEq, Deserialize, Default, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Default, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct Info {
     pub info_id: i64,
     // This is sent as base64 from Go.
     pub name: InfoName,
-    pub info_hash: String,
-    pub age: String,
+    pub info_hash: InfoHash,
+    #[serde(default)]
+    pub info_hash_v2: Option<InfoHash>,
+    pub age: Timestamp,
     // The variant here might need optional fields.
     pub scrape_data: SwarmInfo,
-    // This looks optional in Go.
-    pub scrape_time: String,
+    // Zero if the torrent hasn't been scraped.
+    pub scrape_time: Timestamp,
+}
+
+impl Info {
+    pub fn version(&self) -> TorrentVersion {
+        TorrentVersion::new(&self.info_hash, self.info_hash_v2.as_ref())
+    }
+
+    pub fn info_hashes(&self) -> impl Iterator<Item = InfoHash> {
+        std::iter::once(self.info_hash).chain(self.info_hash_v2)
+    }
 }
 
 pub type FileLength = i64;
 
-#[derive(Clone, PartialEq, Deserialize, Default, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Default, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct File {
-    // This might be optional (for null), and might be sent as byte array that needs manual
-    // splitting.
-    pub path: Option<Vec<String>>,
+    // None for single file torrents.
+    #[serde(
+        default,
+        serialize_with = "file_path::serialize",
+        deserialize_with = "file_path::deserialize"
+    )]
+    pub path: Option<Vec<DecodedBytes>>,
     pub length: FileLength,
 }
 
-// I haven't figured out how to pass this from some app-level/page-level config yet. Leptos seems to
-// infer any missing parts from the context of the page URL. So if the host is missing, it will use
-// the same host etc. Elsewhere in the code we just append stuff to the end of this for now.
-const DHT_INDEXER_URL: &str = if true {
-    // Cove
-    "http://localhost:8080/localDhtIndexer/"
-} else if false {
-    // A Fly instance of the dht-indexer
-    "https://dht-indexer-v2.fly.dev/"
-} else  {
-    // A locally run vanilla dht-indexer?
-    "/dhtindex/"
-};
+#[derive(Clone, Debug, PartialEq)]
+pub struct InfoFilesBatching {
+    // Info hashes per infoFiles request. Keeps GET URLs well under common length limits.
+    pub chunk_size: usize,
+    // How many chunks can be in flight at once.
+    pub concurrency: usize,
+    // Send the info hashes as a form body instead of in the URL. Falls back to GET if the
+    // server doesn't accept it.
+    pub post: bool,
+}
+
+impl InfoFilesBatching {
+    pub fn chunks<'a>(&self, info_hashes: &'a [InfoHash]) -> std::slice::Chunks<'a, InfoHash> {
+        info_hashes.chunks(self.chunk_size.max(1))
+    }
+}
+
+impl Default for InfoFilesBatching {
+    fn default() -> Self {
+        Self {
+            chunk_size: 20,
+            concurrency: 3,
+            post: false,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+pub trait IndexerClient {
+    async fn search(&self, query: String, page: SearchPage) -> Result<InfosSearch>;
+    // Fetches a single batch, see info_files_batching for how callers should split them up.
+    async fn get_info_files(&self, info_hashes: &[InfoHash]) -> Result<InfoFilesPayload>;
+
+    fn info_files_batching(&self) -> InfoFilesBatching {
+        Default::default()
+    }
+}
+
+// Cheap to clone, it's provided through Leptos context and handed to resources.
+#[derive(Clone, Debug, Default)]
+pub struct Indexer<T = DefaultTransport> {
+    pub endpoint: IndexerEndpoint,
+    pub retry: RetryPolicy,
+    pub batching: InfoFilesBatching,
+    // Opt-in, since the indexer has to support it.
+    pub compression: Compression,
+    transport: T,
+    cancel: Option<CancelToken>,
+}
+
+impl<T: Default> Indexer<T> {
+    pub fn new(endpoint: IndexerEndpoint) -> Self {
+        Self::with_transport(endpoint, Default::default())
+    }
+}
+
+impl<T> Indexer<T> {
+    pub fn with_transport(endpoint: IndexerEndpoint, transport: T) -> Self {
+        Self {
+            endpoint,
+            retry: Default::default(),
+            batching: Default::default(),
+            compression: Default::default(),
+            transport,
+            cancel: None,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_batching(mut self, batching: InfoFilesBatching) -> Self {
+        self.batching = batching;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    // A copy of the indexer whose requests are cancelled by the token.
+    pub fn cancellable(&self, cancel: CancelToken) -> Self
+    where
+        T: Clone,
+    {
+        Self {
+            cancel: Some(cancel),
+            ..self.clone()
+        }
+    }
+}
 
-pub async fn search(query: String) -> Result<InfosSearch> {
-    // return Err(GlooError("shit".to_string()));
-    let mut url = DHT_INDEXER_URL.to_string();
-    url.push_str("searchInfos?");
-    let url = url::form_urlencoded::Serializer::new(url)
-        .extend_pairs(&[("s", query)])
-        .finish();
-    info!("searching {:?}", url);
-    Ok(Request::get(url.as_ref()).send().await?.json().await?)
+impl<T: Transport> Indexer<T> {
+    async fn send(&self, request: HttpRequest) -> std::result::Result<HttpResponse, Error> {
+        let request = request.cancel(self.cancel.clone());
+        retry::send_with_retry(&self.transport, &self.retry, request).await
+    }
+}
+
+// Transports are interchangeable, the endpoint is what determines the results.
+impl<T> PartialEq for Indexer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.endpoint == other.endpoint
+    }
 }
 
-async fn handle_go_json_response<T: DeserializeOwned>(
-    resp: Response,
+#[async_trait(?Send)]
+impl<T: Transport> IndexerClient for Indexer<T> {
+    async fn search(&self, query: String, page: SearchPage) -> Result<InfosSearch> {
+        let url = url::form_urlencoded::Serializer::new(self.endpoint.url_for("searchInfos?"))
+            .extend_pairs(&[
+                ("s", query),
+                ("offset", page.offset.to_string()),
+                ("limit", page.limit.to_string()),
+            ])
+            .finish();
+        info!("searching {:?}", url);
+        let response = self.send(HttpRequest::get(url)).await?;
+        let search: InfosSearch = handle_go_json_response(response)?;
+        match search.err {
+            Some(err) if !err.is_empty() => Err(Error::Indexer(err).into()),
+            _ => Ok(search),
+        }
+    }
+
+    async fn get_info_files(&self, info_hashes: &[InfoHash]) -> Result<InfoFilesPayload> {
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        form.extend_pairs(info_hashes.iter().map(|ih| ("ih", ih.to_string())));
+        if self.compression != Compression::None {
+            form.append_pair(COMPRESSION_QUERY_PARAM, self.compression.as_str());
+        }
+        let form = form.finish();
+        let url = self.endpoint.url_for("infoFiles");
+        let request = if self.batching.post {
+            // Go parses form bodies into the same place as query parameters.
+            HttpRequest::post(
+                url.clone(),
+                "application/x-www-form-urlencoded",
+                form.clone(),
+            )
+        } else {
+            HttpRequest::get(format!("{}?{}", url, form))
+        };
+        let mut response = self.send(info_files_headers(request)).await?;
+        if self.batching.post && matches!(response.status, 404 | 405 | 501) {
+            info!("indexer doesn't accept POST for infoFiles, falling back to GET");
+            let request = HttpRequest::get(format!("{}?{}", url, form));
+            response = self.send(info_files_headers(request)).await?;
+        }
+        if response.ok() && self.compression != Compression::None {
+            response.body = compression::decompress(response.body)?;
+        }
+        handle_go_json_response(response).map_err(Into::into)
+    }
+
+    fn info_files_batching(&self) -> InfoFilesBatching {
+        self.batching.clone()
+    }
+}
+
+// Accept-Encoding is off limits to fetch, so compression is requested with a query parameter
+// instead. See Compression.
+fn info_files_headers(request: HttpRequest) -> HttpRequest {
+    request.header("Accept", "application/json")
+}
+
+fn handle_go_json_response<T: DeserializeOwned>(
+    resp: HttpResponse,
 ) -> std::result::Result<T, Error> {
     if !resp.ok() {
-        return Err(Error::Anyhow(anyhow::anyhow!(std::str::from_utf8(
-            &resp.binary().await?
-        )
-        .map_err(anyhow::Error::new)?
-        .to_owned())));
-    }
-    resp.json().await.map_err(Into::into)
-}
-
-pub async fn get_info_files(info_hashes: &[String]) -> Result<InfoFilesPayload> {
-    let mut url = DHT_INDEXER_URL.to_string();
-    url.push_str("infoFiles?");
-    let url = url::form_urlencoded::Serializer::new(url)
-        .extend_pairs(info_hashes.iter().map(|ih| ("ih", ih)))
-        .finish();
-    let response = Request::get(url.as_ref())
-        .header("Accept", "application/json")
-        // I think this gets clobbered by the JS fetch API. I also doubt that lz4 is a valid
-        // encoding for browsers by default.
-        .header("Accept-Encoding", "lz4, br")
-        .send()
-        .await
-        .map_err(Into::into)
-        .map_err(Arc::new)?;
-    handle_go_json_response(response).await.map_err(Into::into)
+        // Go's http.Error sends plain text with a trailing newline.
+        return Err(Error::HttpStatus {
+            status: resp.status,
+            body: String::from_utf8_lossy(&resp.body).trim_end().to_owned(),
+        });
+    }
+    decode_json(&resp.body)
+}
+
+// Includes where in the document decoding failed, the payloads are big enough that the line and
+// column alone aren't much help.
+fn decode_json<T: DeserializeOwned>(body: &[u8]) -> std::result::Result<T, Error> {
+    serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_slice(body)).map_err(
+        |err| Error::Decode {
+            path: err.path().to_string(),
+            source: err.into_inner(),
+        },
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::magnet::Magnet;
 
     #[test_log::test]
     fn test_deserialize_info_files() -> serde_json::Result<()> {
-        let data = r#"
-        [
-          {
-            "Info": {
-              "InfoId": 9223354404497263000,
-              "Name": "VGhlLkludGVybmV0cy5Pd24uQm95LlRoZS5TdG9yeS5vZi5BYXJvbi5Td2FydHouNzIwcC5IRFJpcC54MjY0LkFBQy5NVkdyb3VwLm9yZy5tcDQ=",
-              "InfoHash": "40f3761b9080949ca6ffed3522ad872bc0bef41b",
-              "Age": "2022-11-11T01:47:42Z",
-              "ScrapeData": {
-                "Completed": 44,
-                "Seeders": 16,
-                "Leechers": 1
-              },
-              "ScrapeTime": "2023-05-08T15:05:43Z"
-            },
-            "Files": [
-              {
-                "Path": null,
-                "Length": 1701564468
-              }
-            ]
-          }
-        ]
-        "#;
+        let data =
+            include_str!("../testdata/40f3761b9080949ca6ffed3522ad872bc0bef41b.infoFiles.json");
         let v: InfoFilesPayload = serde_json::from_str(data)?;
         assert_eq!(v.len(), 1);
         assert_eq!(v[0].files.len(), 1);
@@ -200,4 +458,196 @@ mod tests {
         assert_eq!(format!("{:?}", v[0].info.name), format!("{:?}","The.Internets.Own.Boy.The.Story.of.Aaron.Swartz.720p.HDRip.x264.AAC.MVGroup.org.mp4"));
         Ok(())
     }
+
+    // What's saved in IndexedDB is read back the same way as the indexer's responses, so it has
+    // to come out the same as it went in.
+    #[test]
+    fn test_info_files_round_trip() {
+        let testdata = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata");
+        let mut payloads: Vec<String> = std::fs::read_dir(testdata)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().ends_with(".infoFiles.json"))
+            .map(|path| std::fs::read_to_string(path).unwrap())
+            .collect();
+        assert!(!payloads.is_empty());
+        // A hybrid and a v2 only torrent, with Latin-1 names in the paths and file trees.
+        payloads.push(
+            r#"[
+                {
+                    "Info": {"InfoId": 2, "Name": "dGVzdA==",
+                        "InfoHash": "631a31dd0a46257d5078c0dee4e66e26f73e42ac",
+                        "InfoHashV2": "1220caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e",
+                        "Age": "2023-01-02T03:04:05Z",
+                        "ScrapeData": {"Seeders": 1, "Completed": 2, "Leechers": 3},
+                        "ScrapeTime": "0001-01-01T00:00:00Z"},
+                    "Files": [{"Path": "QW3pbGllL+kudHh0", "Length": 1}],
+                    "FileTree": {"\u0000QW3pbGll": {"\u00006S50eHQ=": {"": {"length": 1, "pieces root": "00"}}}}
+                },
+                {
+                    "Info": {"InfoId": 3, "Name": "dGVzdA==",
+                        "InfoHash": "1220caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e",
+                        "Age": null,
+                        "ScrapeData": {"Seeders": 0, "Completed": 0, "Leechers": 0},
+                        "ScrapeTime": "2023-05-08T15:05:43Z"},
+                    "Files": [],
+                    "FileTree": {"a": {"b.txt": {"": {"length": 2}}}, "\u00006S50eHQ=": {"": {"length": 3}}}
+                }
+            ]"#
+            .to_owned(),
+        );
+        for json in payloads {
+            let payload: InfoFilesPayload = serde_json::from_str(&json).unwrap();
+            let saved = serde_json::to_string(&payload).unwrap();
+            assert_eq!(
+                serde_json::from_str::<InfoFilesPayload>(&saved).unwrap(),
+                payload
+            );
+            // v2 only torrents get their files from the tree, so the files aren't stored too.
+            for info_files in payload
+                .iter()
+                .filter(|info_files| info_files.info.version() == TorrentVersion::V2)
+            {
+                let stored = serde_json::to_string(info_files).unwrap();
+                let expected = format!(
+                    r#"{{"Info":{},"Files":[],"FileTree":{}}}"#,
+                    serde_json::to_string(&info_files.info).unwrap(),
+                    serde_json::to_string(&info_files.file_tree).unwrap()
+                );
+                assert_eq!(stored.len(), expected.len());
+                assert_eq!(stored, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_v2_and_hybrid() {
+        let v2_hash = "1220caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e";
+        let v1_hash = "631a31dd0a46257d5078c0dee4e66e26f73e42ac";
+        let v2_only: InfoFiles = serde_json::from_str(&format!(
+            r#"{{
+                "Info": {{"InfoId": 1, "Name": "dGVzdA==", "InfoHash": "{}", "Age": null,
+                    "ScrapeData": {{"Seeders": 0, "Completed": 0, "Leechers": 0}},
+                    "ScrapeTime": null}},
+                "Files": [],
+                "FileTree": {{"test": {{"a.txt": {{"": {{"length": 1}}}}}}}}
+            }}"#,
+            v2_hash
+        ))
+        .unwrap();
+        assert_eq!(v2_only.info.version(), TorrentVersion::V2);
+        let paths: Vec<String> = v2_only
+            .upverted_files()
+            .iter()
+            .map(|file| {
+                let path: Vec<String> = file.path.iter().map(ToString::to_string).collect();
+                path.join("/")
+            })
+            .collect();
+        assert_eq!(paths, ["test/a.txt"]);
+        assert_eq!(
+            Magnet::new(v2_only.info.info_hashes()).to_string(),
+            format!("magnet:?xt=urn:btmh:{}", v2_hash)
+        );
+        let hybrid: InfoItem = serde_json::from_str(&format!(
+            r#"{{"InfoHash": "{}", "InfoHashV2": "{}", "Name": "test",
+                "SwarmInfo": {{"Seeders": 0, "Completed": 0, "Leechers": 0}},
+                "Size": 1, "Age": null, "NoSwarmInfo": true}}"#,
+            v1_hash, v2_hash
+        ))
+        .unwrap();
+        assert_eq!(hybrid.version(), TorrentVersion::Hybrid);
+        assert_eq!(
+            Magnet::new(hybrid.info_hashes()).to_string(),
+            format!("magnet:?xt=urn:btih:{}&xt=urn:btmh:{}", v1_hash, v2_hash)
+        );
+    }
+
+    #[derive(Default)]
+    struct StaticTransport {
+        response: HttpResponse,
+        requests: std::cell::RefCell<Vec<HttpRequest>>,
+    }
+
+    #[async_trait(?Send)]
+    impl Transport for StaticTransport {
+        async fn send(&self, request: HttpRequest) -> std::result::Result<HttpResponse, Error> {
+            self.requests.borrow_mut().push(request);
+            Ok(self.response.clone())
+        }
+    }
+
+    fn static_indexer(status: u16, body: &str) -> Indexer<StaticTransport> {
+        Indexer::with_transport(
+            IndexerEndpoint::new("http://indexer.test/"),
+            StaticTransport {
+                response: HttpResponse {
+                    status,
+                    body: body.as_bytes().to_vec(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_search_request() {
+        let indexer = static_indexer(200, r#"{"Total": 0, "Err": null, "Items": []}"#);
+        let page = SearchPage::at_offset(100);
+        let result =
+            futures::executor::block_on(indexer.search("big buck".to_owned(), page)).unwrap();
+        assert_eq!(result.total, 0);
+        assert_eq!(
+            indexer.transport.requests.borrow()[0].url,
+            "http://indexer.test/searchInfos?s=big+buck&offset=100&limit=50"
+        );
+    }
+
+    #[test]
+    fn test_search_go_error() {
+        let indexer = static_indexer(200, r#"{"Total": 0, "Err": "bad query", "Items": []}"#);
+        let err = futures::executor::block_on(indexer.search("(".to_owned(), SearchPage::first()))
+            .unwrap_err();
+        assert!(matches!(&**err, Error::Indexer(msg) if msg == "bad query"));
+    }
+
+    fn info_hashes(count: u8) -> Vec<InfoHash> {
+        (0..count).map(|i| InfoHash::V1([i; 20])).collect()
+    }
+
+    #[test]
+    fn test_decode_error_path() {
+        let indexer = static_indexer(200, r#"[{"Info": {"InfoId": "nope"}, "Files": []}]"#);
+        let err = futures::executor::block_on(indexer.get_info_files(&info_hashes(1))).unwrap_err();
+        assert!(matches!(&**err, Error::Decode { path, .. } if path == "[0].Info.InfoId"));
+    }
+
+    #[test]
+    fn test_info_files_chunks() {
+        let chunks: Vec<usize> = InfoFilesBatching::default()
+            .chunks(&info_hashes(45))
+            .map(<[InfoHash]>::len)
+            .collect();
+        assert_eq!(chunks, [20, 20, 5]);
+    }
+
+    #[test]
+    fn test_get_info_files_error_status() {
+        let indexer = static_indexer(500, "database is locked");
+        let info_hashes = [
+            "40F3761B9080949CA6FFED3522AD872BC0BEF41B".parse().unwrap(),
+            "3670d38c31d660d690384731483e145695586797".parse().unwrap(),
+        ];
+        let err = futures::executor::block_on(indexer.get_info_files(&info_hashes)).unwrap_err();
+        assert!(matches!(
+            &**err,
+            Error::HttpStatus { status: 500, body } if body == "database is locked"
+        ));
+        assert_eq!(
+            indexer.transport.requests.borrow()[0].url,
+            "http://indexer.test/infoFiles?ih=40f3761b9080949ca6ffed3522ad872bc0bef41b\
+             &ih=3670d38c31d660d690384731483e145695586797"
+        );
+    }
 }
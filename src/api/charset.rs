@@ -0,0 +1,224 @@
+use encoding_rs::{Encoding, UTF_8};
+use std::cmp::Ordering;
+use std::fmt::{Debug, Formatter, Write};
+use std::hash::{Hash, Hasher};
+
+// Torrent metadata doesn't declare a charset. Most clients use UTF-8 now, but plenty of older
+// torrents were made with the system code page (Shift-JIS, GBK, CP1251, Latin-1...), so anything
+// else is guessed from the bytes.
+#[derive(Clone, PartialEq, Debug)]
+struct Decoded {
+    text: String,
+    encoding: &'static Encoding,
+    // The guess was low confidence, or the result has characters that suggest it's wrong.
+    doubtful: bool,
+}
+
+// Raw bytes from the info dict, and our best reading of them. Decoding is done once up front
+// since these are displayed and compared a lot. Equality, ordering and hashing go by the bytes.
+#[derive(Clone)]
+pub struct DecodedBytes {
+    bytes: Vec<u8>,
+    decoded: Decoded,
+}
+
+impl DecodedBytes {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self::from_parts(vec![bytes]).pop().unwrap()
+    }
+
+    // For things that share an encoding, like the components of a path. The guess is made from
+    // all of them together, single components are often too short to go on.
+    pub fn from_parts(parts: Vec<Vec<u8>>) -> Vec<Self> {
+        if parts.iter().all(|part| std::str::from_utf8(part).is_ok()) {
+            return parts
+                .into_iter()
+                .map(|bytes| Self {
+                    decoded: decode_with(&bytes, UTF_8, true),
+                    bytes,
+                })
+                .collect();
+        }
+        let mut detector = chardetng::EncodingDetector::new();
+        for part in &parts {
+            detector.feed(part, false);
+            detector.feed(b"/", false);
+        }
+        detector.feed(&[], true);
+        let (encoding, confident) = detector.guess_assess(None, false);
+        parts
+            .into_iter()
+            .map(|bytes| Self {
+                decoded: decode_with(&bytes, encoding, confident),
+                bytes,
+            })
+            .collect()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    // Lossy if the bytes weren't valid in the guessed encoding.
+    pub fn as_str(&self) -> &str {
+        &self.decoded.text
+    }
+
+    pub fn encoding_name(&self) -> &'static str {
+        self.decoded.encoding.name()
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        self.decoded.encoding == UTF_8
+    }
+
+    // When set it's worth showing raw_hex alongside the text.
+    pub fn is_doubtful(&self) -> bool {
+        self.decoded.doubtful
+    }
+
+    pub fn raw_hex(&self) -> String {
+        to_hex(&self.bytes)
+    }
+}
+
+fn decode_with(bytes: &[u8], encoding: &'static Encoding, confident: bool) -> Decoded {
+    // Never fails, undecodable sequences become U+FFFD.
+    let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+    Decoded {
+        doubtful: !confident || had_errors || has_suspect_chars(&text),
+        text: text.into_owned(),
+        encoding,
+    }
+}
+
+fn has_suspect_chars(text: &str) -> bool {
+    text.chars()
+        .any(|c| c == char::REPLACEMENT_CHARACTER || c.is_control())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+impl Default for DecodedBytes {
+    fn default() -> Self {
+        Self::from_bytes(vec![])
+    }
+}
+
+impl PartialEq for DecodedBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for DecodedBytes {}
+
+impl PartialOrd for DecodedBytes {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DecodedBytes {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+impl Hash for DecodedBytes {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state)
+    }
+}
+
+impl PartialEq<str> for DecodedBytes {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl std::fmt::Display for DecodedBytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for DecodedBytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl AsRef<str> for DecodedBytes {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<String> for DecodedBytes {
+    fn from(value: String) -> Self {
+        Self::from_bytes(value.into_bytes())
+    }
+}
+
+impl From<&str> for DecodedBytes {
+    fn from(value: &str) -> Self {
+        value.to_owned().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1251};
+
+    #[test]
+    fn test_utf8_passes_through() {
+        let decoded = DecodedBytes::from("Amélie (2001) 1080p");
+        assert_eq!(decoded.as_str(), "Amélie (2001) 1080p");
+        assert!(decoded.is_utf8());
+        assert!(!decoded.is_doubtful());
+    }
+
+    #[test]
+    fn test_legacy_encodings() {
+        for (encoding, text) in [
+            (SHIFT_JIS, "千と千尋の神隠し 日本語字幕付き 完全版"),
+            (WINDOWS_1251, "Война и мир. Полная версия фильма на русском"),
+        ] {
+            let (bytes, _, _) = encoding.encode(text);
+            assert!(std::str::from_utf8(&bytes).is_err());
+            let decoded = DecodedBytes::from_bytes(bytes.into_owned());
+            assert_eq!(decoded.as_str(), text);
+            assert_eq!(decoded.encoding_name(), encoding.name());
+        }
+    }
+
+    #[test]
+    fn test_parts_share_encoding() {
+        let parts = ["Война и мир", "Полная версия фильма на русском", "1.avi"];
+        let parts = parts
+            .iter()
+            .map(|part| WINDOWS_1251.encode(part).0.into_owned())
+            .collect();
+        let decoded = DecodedBytes::from_parts(parts);
+        // The last part is plain ASCII, but it's still read the same way as the rest.
+        assert!(decoded
+            .iter()
+            .all(|part| part.encoding_name() == "windows-1251"));
+        assert_eq!(decoded[1].as_str(), "Полная версия фильма на русском");
+    }
+
+    #[test]
+    fn test_garbage_is_doubtful() {
+        let decoded = DecodedBytes::from_bytes(vec![0x00, 0xff, 0x81, 0x01]);
+        assert!(decoded.is_doubtful());
+        assert_eq!(decoded.raw_hex(), "00ff8101");
+    }
+}
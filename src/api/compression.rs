@@ -0,0 +1,144 @@
+use super::endpoint::local_storage;
+use crate::Error;
+use log::warn;
+use std::io::Read;
+
+const LOCAL_STORAGE_KEY: &str = "dhtsearch.compression";
+// Sent as a query parameter rather than a header, so it doesn't cost a CORS preflight.
+pub const COMPRESSION_QUERY_PARAM: &str = "encoding";
+
+const LZ4_FRAME_MAGIC: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+const ZSTD_FRAME_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// Compression we ask the indexer to apply to infoFiles bodies. Browsers won't decode these
+// themselves, so we do it after reading the raw body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 3] = [Self::None, Self::Lz4, Self::Zstd];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Lz4 => "lz4",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == value)
+    }
+
+    pub fn load() -> Self {
+        local_storage()
+            .and_then(|storage| storage.get_item(LOCAL_STORAGE_KEY).ok().flatten())
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    pub fn save(self) {
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        if let Err(err) = storage.set_item(LOCAL_STORAGE_KEY, self.as_str()) {
+            warn!("saving compression setting: {:?}", err);
+        }
+    }
+}
+
+// Servers that don't know the encoding parameter send plain JSON, so we go by the frame magic
+// rather than trusting what we asked for. That also avoids needing the server to expose a
+// response header to CORS.
+pub(super) fn decompress(body: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let decompress_error = |encoding: &'static str| {
+        move |err: std::io::Error| Error::Decompress {
+            encoding,
+            message: err.to_string(),
+        }
+    };
+    let mut decompressed = vec![];
+    if body.starts_with(&LZ4_FRAME_MAGIC) {
+        lz4_flex::frame::FrameDecoder::new(body.as_slice())
+            .read_to_end(&mut decompressed)
+            .map_err(decompress_error("lz4"))?;
+    } else if body.starts_with(&ZSTD_FRAME_MAGIC) {
+        ruzstd::StreamingDecoder::new(body.as_slice())
+            .map_err(|err| Error::Decompress {
+                encoding: "zstd",
+                message: err.to_string(),
+            })?
+            .read_to_end(&mut decompressed)
+            .map_err(decompress_error("zstd"))?;
+    } else {
+        return Ok(body);
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_decompress_lz4_frame() {
+        let json = br#"[{"Info": {}, "Files": []}]"#;
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(vec![]);
+        encoder.write_all(json).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.starts_with(&LZ4_FRAME_MAGIC));
+        assert_eq!(decompress(compressed).unwrap(), json);
+    }
+
+    #[test]
+    fn test_decompress_zstd_frame() {
+        // A single raw (stored) block, no checksum.
+        let compressed = [
+            0x28, 0xb5, 0x2f, 0xfd, // magic
+            0x20, // frame header descriptor: single segment
+            0x05, // content size
+            0x29, 0x00, 0x00, // last block, raw, 5 bytes
+            b'[', b'1', b',', b'2', b']',
+        ];
+        assert_eq!(decompress(compressed.to_vec()).unwrap(), b"[1,2]");
+    }
+
+    #[test]
+    fn test_plain_json_passes_through() {
+        let json = br#"[]"#.to_vec();
+        assert_eq!(decompress(json.clone()).unwrap(), json);
+    }
+
+    #[test]
+    fn test_truncated_frame() {
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(vec![]);
+        encoder.write_all(&[b'x'; 1000]).unwrap();
+        let mut compressed = encoder.finish().unwrap();
+        compressed.truncate(8);
+        assert!(matches!(
+            decompress(compressed),
+            Err(Error::Decompress {
+                encoding: "lz4",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_zstd_frame() {
+        let compressed = vec![0x28, 0xb5, 0x2f, 0xfd, 0x20, 0x05, 0x29, 0x00, 0x00, b'['];
+        assert!(matches!(
+            decompress(compressed),
+            Err(Error::Decompress {
+                encoding: "zstd",
+                ..
+            })
+        ));
+    }
+}
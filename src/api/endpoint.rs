@@ -0,0 +1,168 @@
+use log::{info, warn};
+use std::fmt::{Display, Formatter};
+
+// Query parameter on the page URL that overrides everything else, handy for sharing a link that
+// points at a particular indexer.
+pub const ENDPOINT_QUERY_PARAM: &str = "indexer";
+const LOCAL_STORAGE_KEY: &str = "dhtsearch.indexer";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointPreset {
+    Cove,
+    // A hosted instance of the dht-indexer.
+    Fly,
+    // A locally run vanilla dht-indexer reverse proxied on the same origin.
+    SameOrigin,
+}
+
+impl EndpointPreset {
+    pub const ALL: [EndpointPreset; 3] = [Self::Cove, Self::Fly, Self::SameOrigin];
+
+    pub fn url(self) -> &'static str {
+        match self {
+            Self::Cove => "http://localhost:8080/localDhtIndexer/",
+            Self::Fly => "https://dht-indexer-v2.fly.dev/",
+            Self::SameOrigin => "/dhtindex/",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Cove => "Cove (localhost:8080)",
+            Self::Fly => "Hosted dht-indexer",
+            Self::SameOrigin => "Same origin (/dhtindex/)",
+        }
+    }
+
+    pub fn from_url(url: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.url() == url)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexerEndpoint(String);
+
+impl IndexerEndpoint {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        let mut base_url = base_url.into();
+        // Endpoint paths are appended directly, so the base must look like a directory.
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        Self(base_url)
+    }
+
+    // Set DHT_INDEXER_URL when building to change the default for a deployment.
+    pub fn build_default() -> Self {
+        Self::new(option_env!("DHT_INDEXER_URL").unwrap_or(EndpointPreset::Cove.url()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn preset(&self) -> Option<EndpointPreset> {
+        EndpointPreset::from_url(&self.0)
+    }
+
+    // Leptos and the browser fill in anything missing from the page URL, so a base without a
+    // host (like the same origin preset) is fine here.
+    pub fn url_for(&self, path: &str) -> String {
+        let mut url = self.0.clone();
+        url.push_str(path);
+        url
+    }
+
+    // Resolves the endpoint from the page URL, then localStorage, then the build-time default.
+    pub fn resolve() -> Self {
+        if let Some(endpoint) = from_page_url() {
+            info!("using indexer {} from page url", endpoint);
+            return endpoint;
+        }
+        if let Some(endpoint) = load() {
+            info!("using indexer {} from local storage", endpoint);
+            return endpoint;
+        }
+        Self::build_default()
+    }
+
+    pub fn save(&self) {
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        if let Err(err) = storage.set_item(LOCAL_STORAGE_KEY, self.as_str()) {
+            warn!("saving indexer endpoint: {:?}", err);
+        }
+    }
+
+    pub fn clear_saved() {
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        if let Err(err) = storage.remove_item(LOCAL_STORAGE_KEY) {
+            warn!("clearing saved indexer endpoint: {:?}", err);
+        }
+    }
+}
+
+impl Default for IndexerEndpoint {
+    fn default() -> Self {
+        Self::build_default()
+    }
+}
+
+impl Display for IndexerEndpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+pub(crate) fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+fn load() -> Option<IndexerEndpoint> {
+    local_storage()?
+        .get_item(LOCAL_STORAGE_KEY)
+        .ok()
+        .flatten()
+        .filter(|value| !value.is_empty())
+        .map(IndexerEndpoint::new)
+}
+
+fn from_page_url() -> Option<IndexerEndpoint> {
+    let href = web_sys::window()?.location().href().ok()?;
+    endpoint_from_query(&href)
+}
+
+fn endpoint_from_query(href: &str) -> Option<IndexerEndpoint> {
+    url::Url::parse(href)
+        .ok()?
+        .query_pairs()
+        .find(|(key, _)| key == ENDPOINT_QUERY_PARAM)
+        .map(|(_, value)| value.into_owned())
+        .filter(|value| !value.is_empty())
+        .map(IndexerEndpoint::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_from_query() {
+        assert_eq!(
+            endpoint_from_query("http://localhost:8081/?indexer=https%3A%2F%2Fexample.com%2Fdht"),
+            Some(IndexerEndpoint::new("https://example.com/dht/"))
+        );
+        assert_eq!(endpoint_from_query("http://localhost:8081/?s=ubuntu"), None);
+        assert_eq!(endpoint_from_query("http://localhost:8081/?indexer="), None);
+    }
+
+    #[test]
+    fn test_presets_round_trip() {
+        for preset in EndpointPreset::ALL {
+            assert_eq!(IndexerEndpoint::new(preset.url()).preset(), Some(preset));
+        }
+    }
+}
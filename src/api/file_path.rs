@@ -0,0 +1,97 @@
+use super::charset::DecodedBytes;
+use base64::Engine;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// The indexer sends paths as a list of components when they're valid UTF-8, and otherwise as the
+// raw bytes of the whole path joined with '/', base64 encoded like any Go []byte. null is a single
+// file torrent.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawPath {
+    Components(Vec<String>),
+    Joined(String),
+}
+
+pub(super) fn deserialize<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<Vec<DecodedBytes>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let parts: Vec<Vec<u8>> = match Option::<RawPath>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(RawPath::Components(components)) => {
+            components.into_iter().map(String::into_bytes).collect()
+        }
+        Some(RawPath::Joined(base64)) => ::base64::engine::general_purpose::STANDARD
+            .decode(base64)
+            .map_err(|err| D::Error::custom(err.to_string()))?
+            .split(|&byte| byte == b'/')
+            .map(<[u8]>::to_vec)
+            .collect(),
+    };
+    Ok(Some(DecodedBytes::from_parts(parts)))
+}
+
+// Back into whichever form the indexer would have sent, so it reads back the same.
+pub(super) fn serialize<S>(
+    path: &Option<Vec<DecodedBytes>>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let raw = path.as_ref().map(|parts| {
+        let components: Option<Vec<String>> = parts
+            .iter()
+            .map(|part| String::from_utf8(part.as_bytes().to_vec()).ok())
+            .collect();
+        components.map(RawPath::Components).unwrap_or_else(|| {
+            let joined: Vec<&[u8]> = parts.iter().map(DecodedBytes::as_bytes).collect();
+            RawPath::Joined(::base64::engine::general_purpose::STANDARD.encode(joined.join(&b'/')))
+        })
+    });
+    raw.serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::File;
+
+    fn path(json: &str) -> Option<Vec<String>> {
+        let file: File =
+            serde_json::from_str(&format!(r#"{{"Path": {}, "Length": 1}}"#, json)).unwrap();
+        file.path
+            .map(|path| path.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn test_path_forms() {
+        assert_eq!(path("null"), None);
+        assert_eq!(
+            path(r#"["a", "b.mkv"]"#),
+            Some(vec!["a".into(), "b.mkv".into()])
+        );
+        // "Amélie/é.txt" in Latin-1.
+        assert_eq!(
+            path(r#""QW3pbGllL+kudHh0""#),
+            Some(vec!["Amélie".into(), "é.txt".into()])
+        );
+        let file: File =
+            serde_json::from_str(r#"{"Path": "QW3pbGllL+kudHh0", "Length": 1}"#).unwrap();
+        assert_eq!(file.path.unwrap()[1].as_bytes(), b"\xe9.txt");
+    }
+
+    #[test]
+    fn test_path_round_trip() {
+        for json in [
+            r#"{"Path":null,"Length":1}"#,
+            r#"{"Path":["a","b.mkv"],"Length":1}"#,
+            r#"{"Path":"QW3pbGllL+kudHh0","Length":1}"#,
+        ] {
+            let file: File = serde_json::from_str(json).unwrap();
+            assert_eq!(serde_json::to_string(&file).unwrap(), json);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use super::{DecodedBytes, File, FileLength};
+use base64::Engine;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+// The BEP 52 "file tree" from v2 and hybrid info dicts, passed through by the indexer as JSON.
+// Directories are maps of names to nodes, and a file is a map with a single empty key holding its
+// details. Names are kept as raw bytes, see DecodedBytes.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub struct FileTree(#[serde(with = "raw_names")] pub BTreeMap<Vec<u8>, FileTreeNode>);
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum FileTreeNode {
+    File {
+        #[serde(rename = "")]
+        file: FileTreeFile,
+    },
+    Dir(#[serde(with = "raw_names")] BTreeMap<Vec<u8>, FileTreeNode>),
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct FileTreeFile {
+    pub length: FileLength,
+    // Hex encoded. Missing for empty files.
+    #[serde(rename = "pieces root", default)]
+    pub pieces_root: Option<String>,
+}
+
+impl FileTree {
+    // Flattened into the same shape as a v1 file list. The map is ordered by the name bytes, which
+    // is the order BEP 52 requires, so this matches what clients see.
+    pub fn files(&self) -> Vec<File> {
+        let mut files = vec![];
+        flatten(&self.0, &mut vec![], &mut files);
+        files
+    }
+}
+
+fn flatten(dir: &BTreeMap<Vec<u8>, FileTreeNode>, path: &mut Vec<Vec<u8>>, files: &mut Vec<File>) {
+    for (name, node) in dir {
+        path.push(name.clone());
+        match node {
+            FileTreeNode::File { file } => files.push(File {
+                path: Some(DecodedBytes::from_parts(path.clone())),
+                length: file.length,
+            }),
+            FileTreeNode::Dir(children) => flatten(children, path, files),
+        }
+        path.pop();
+    }
+}
+
+// JSON keys have to be strings, so names that aren't UTF-8 are written as base64 after a NUL,
+// which can't be in a real name.
+mod raw_names {
+    use super::*;
+
+    const RAW_PREFIX: char = '\0';
+
+    pub(super) fn serialize<S>(
+        dir: &BTreeMap<Vec<u8>, FileTreeNode>,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(dir.iter().map(|(name, node)| (encode(name), node)))
+    }
+
+    pub(super) fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> std::result::Result<BTreeMap<Vec<u8>, FileTreeNode>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        BTreeMap::<String, FileTreeNode>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(name, node)| Ok((decode(name).map_err(D::Error::custom)?, node)))
+            .collect()
+    }
+
+    fn encode(name: &[u8]) -> String {
+        match std::str::from_utf8(name) {
+            Ok(name) if !name.starts_with(RAW_PREFIX) => name.to_owned(),
+            _ => format!(
+                "{}{}",
+                RAW_PREFIX,
+                ::base64::engine::general_purpose::STANDARD.encode(name)
+            ),
+        }
+    }
+
+    fn decode(name: String) -> Result<Vec<u8>, base64::DecodeError> {
+        match name.strip_prefix(RAW_PREFIX) {
+            Some(base64) => ::base64::engine::general_purpose::STANDARD.decode(base64),
+            None => Ok(name.into_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten() {
+        let tree: FileTree = serde_json::from_str(
+            r#"{
+                "b.txt": {"": {"length": 3, "pieces root": "00"}},
+                "a": {
+                    "2.txt": {"": {"length": 2}},
+                    "1.txt": {"": {"length": 1, "pieces root": "00"}}
+                }
+            }"#,
+        )
+        .unwrap();
+        let files: Vec<(Vec<String>, FileLength)> = tree
+            .files()
+            .into_iter()
+            .map(|file| {
+                let path = file.path.unwrap().iter().map(ToString::to_string).collect();
+                (path, file.length)
+            })
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (vec!["a".to_owned(), "1.txt".to_owned()], 1),
+                (vec!["a".to_owned(), "2.txt".to_owned()], 2),
+                (vec!["b.txt".to_owned()], 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raw_names() {
+        // "é.txt" in Latin-1.
+        let tree = FileTree(BTreeMap::from([
+            (
+                b"\xe9.txt".to_vec(),
+                FileTreeNode::File {
+                    file: FileTreeFile {
+                        length: 1,
+                        pieces_root: None,
+                    },
+                },
+            ),
+            (
+                b"a.txt".to_vec(),
+                FileTreeNode::File {
+                    file: FileTreeFile {
+                        length: 2,
+                        pieces_root: None,
+                    },
+                },
+            ),
+        ]));
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(
+            json,
+            r#"{"a.txt":{"":{"length":2,"pieces root":null}},"\u00006S50eHQ=":{"":{"length":1,"pieces root":null}}}"#
+        );
+        assert_eq!(serde_json::from_str::<FileTree>(&json).unwrap(), tree);
+        assert_eq!(
+            tree.files()[1].path.as_ref().unwrap()[0].as_bytes(),
+            b"\xe9.txt"
+        );
+    }
+}
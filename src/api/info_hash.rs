@@ -0,0 +1,234 @@
+use crate::Error;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+// The multihash prefix for a 32 byte SHA2-256 digest, which is all BEP 52 uses.
+const SHA2_256_MULTIHASH_PREFIX: [u8; 2] = [0x12, 0x20];
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// An info hash in whatever form it turned up in, stored as raw bytes so different spellings of
+// the same torrent compare and hash equal. Display gives the canonical form: lowercase hex for
+// v1, and the lowercase hex multihash for v2, as they appear in magnet links.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InfoHash {
+    V1([u8; 20]),
+    V2([u8; 32]),
+}
+
+impl InfoHash {
+    pub fn is_v2(&self) -> bool {
+        matches!(self, Self::V2(_))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::V1(bytes) => bytes,
+            Self::V2(bytes) => bytes,
+        }
+    }
+
+    // The exact topic for a magnet link.
+    pub fn magnet_urn(&self) -> String {
+        match self {
+            Self::V1(_) => format!("urn:btih:{}", self),
+            Self::V2(_) => format!("urn:btmh:{}", self),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TorrentVersion {
+    V1,
+    V2,
+    // Has both a v1 and a v2 info hash, and can be downloaded by either kind of client.
+    Hybrid,
+}
+
+impl TorrentVersion {
+    // The indexer puts the v2 hash in the primary field for v2 only torrents.
+    pub fn new(info_hash: &InfoHash, info_hash_v2: Option<&InfoHash>) -> Self {
+        match (info_hash.is_v2(), info_hash_v2) {
+            (true, _) => Self::V2,
+            (false, Some(_)) => Self::Hybrid,
+            (false, None) => Self::V1,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::V1 => "v1",
+            Self::V2 => "v2",
+            Self::Hybrid => "hybrid",
+        }
+    }
+}
+
+// Only here so types holding an info hash can derive Default.
+impl Default for InfoHash {
+    fn default() -> Self {
+        Self::V1([0; 20])
+    }
+}
+
+impl FromStr for InfoHash {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidInfoHash(s.to_owned());
+        let bytes = match s.len() {
+            40 => decode_hex(s),
+            32 => decode_base32(s),
+            // Bare SHA2-256, or one with its multihash prefix.
+            64 | 68 => decode_hex(s),
+            _ => None,
+        }
+        .ok_or_else(invalid)?;
+        match bytes.len() {
+            20 => Ok(Self::V1(bytes.try_into().unwrap())),
+            32 => Ok(Self::V2(bytes.try_into().unwrap())),
+            34 => match bytes.strip_prefix(&SHA2_256_MULTIHASH_PREFIX[..]) {
+                Some(digest) => Ok(Self::V2(digest.try_into().unwrap())),
+                None => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Display for InfoHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_v2() {
+            write_hex(f, &SHA2_256_MULTIHASH_PREFIX)?;
+        }
+        write_hex(f, self.as_bytes())
+    }
+}
+
+impl Debug for InfoHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for InfoHash {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value: String = Deserialize::deserialize(deserializer)?;
+        value.parse().map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for InfoHash {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+fn write_hex(f: &mut Formatter<'_>, bytes: &[u8]) -> std::fmt::Result {
+    bytes.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    // from_str_radix would take a sign, and "+a" isn't a hex byte.
+    if s.len() % 2 != 0 || !s.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// RFC 4648 without padding, which is how 20 byte info hashes show up in old magnet links.
+fn decode_base32(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AARON_SWARTZ: &str = "40f3761b9080949ca6ffed3522ad872bc0bef41b";
+
+    #[test]
+    fn test_parse_v1_forms() {
+        let canonical: InfoHash = AARON_SWARTZ.parse().unwrap();
+        assert_eq!(canonical.to_string(), AARON_SWARTZ);
+        let upper: InfoHash = AARON_SWARTZ.to_uppercase().parse().unwrap();
+        assert_eq!(upper, canonical);
+        let base32: InfoHash = "IDZXMG4QQCKJZJX75U2SFLMHFPAL55A3".parse().unwrap();
+        assert_eq!(base32, canonical);
+        let lower_base32: InfoHash = "idzxmg4qqckjzjx75u2sflmhfpal55a3".parse().unwrap();
+        assert_eq!(lower_base32, canonical);
+        assert_eq!(canonical.magnet_urn(), format!("urn:btih:{}", AARON_SWARTZ));
+    }
+
+    #[test]
+    fn test_parse_v2_forms() {
+        let digest = "caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e";
+        let multihash = format!("1220{}", digest);
+        let from_multihash: InfoHash = multihash.parse().unwrap();
+        let from_digest: InfoHash = digest.parse().unwrap();
+        assert_eq!(from_multihash, from_digest);
+        assert!(from_multihash.is_v2());
+        assert_eq!(from_multihash.to_string(), multihash);
+        assert_eq!(
+            from_multihash.magnet_urn(),
+            format!("urn:btmh:{}", multihash)
+        );
+        // Some other hash function.
+        assert!(format!("1320{}", digest).parse::<InfoHash>().is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for s in [
+            "",
+            "a",
+            &AARON_SWARTZ[1..],
+            "zz",
+            "IDZXMG4QQCKJZJX75U2SFLMHFPAL55A1",
+            &"+a".repeat(20),
+        ] {
+            assert!(
+                matches!(s.parse::<InfoHash>(), Err(Error::InvalidInfoHash(_))),
+                "{:?}",
+                s
+            );
+        }
+        // Non-ASCII mustn't panic when slicing.
+        assert!("é".repeat(20).parse::<InfoHash>().is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let json = format!(r#""{}""#, AARON_SWARTZ.to_uppercase());
+        let info_hash: InfoHash = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            serde_json::to_string(&info_hash).unwrap(),
+            format!(r#""{}""#, AARON_SWARTZ)
+        );
+        assert!(serde_json::from_str::<InfoHash>(r#""nope""#).is_err());
+    }
+}
@@ -1,14 +1,19 @@
+use super::charset::DecodedBytes;
 use base64::Engine;
 use serde::de::Error as _;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
 
+// A torrent name, see DecodedBytes for how it's read.
 #[derive(Clone, PartialEq, Default)]
-pub struct InfoName(Vec<u8>);
+pub struct InfoName(DecodedBytes);
 
-impl InfoName {
-    pub fn as_str(&self) -> &str {
-        unsafe { std::str::from_utf8_unchecked(&self.0) }
+impl Deref for InfoName {
+    type Target = DecodedBytes;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
 }
 
@@ -32,7 +37,7 @@ impl Debug for InfoName {
 
 impl AsRef<str> for InfoName {
     fn as_ref(&self) -> &str {
-        std::str::from_utf8(&self.0).unwrap()
+        self.as_str()
     }
 }
 
@@ -42,16 +47,49 @@ impl<'de> Deserialize<'de> for InfoName {
         D: Deserializer<'de>,
     {
         let v: String = Deserialize::deserialize(deserializer)?;
-        Ok(InfoName(
+        Ok(InfoName(DecodedBytes::from_bytes(
             ::base64::engine::general_purpose::STANDARD
                 .decode(v)
                 .map_err(|err| D::Error::custom(err.to_string()))?,
-        ))
+        )))
+    }
+}
+
+// Base64 again, the same as it arrived.
+impl Serialize for InfoName {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer
+            .serialize_str(&::base64::engine::general_purpose::STANDARD.encode(self.as_bytes()))
     }
 }
 
 impl From<String> for InfoName {
     fn from(value: String) -> Self {
-        Self(value.into_bytes())
+        Self(value.into())
+    }
+}
+
+impl From<DecodedBytes> for InfoName {
+    fn from(value: DecodedBytes) -> Self {
+        Self(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_utf8_name() {
+        // "Amélie" in Latin-1, base64 encoded as Go sends []byte.
+        let name: InfoName = serde_json::from_str(r#""QW3pbGll""#).unwrap();
+        assert_eq!(name.as_bytes(), b"Am\xe9lie");
+        assert_eq!(name.as_str(), "Amélie");
+        assert!(!name.is_utf8());
+        assert_eq!(name.raw_hex(), "416de96c6965");
+        assert_eq!(serde_json::to_string(&name).unwrap(), r#""QW3pbGll""#);
     }
 }
@@ -0,0 +1,225 @@
+use super::transport::{HttpRequest, HttpResponse, Transport};
+use crate::{is_transient_status, Error};
+use log::info;
+use std::time::Duration;
+
+// Only used for GETs, which are all idempotent against the indexer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    // Including the first attempt, so 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    // Also the longest Retry-After we'll wait for, beyond that we give up.
+    pub max_delay: Duration,
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    // Delay after the given failed attempt, starting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let delay = exponential.min(self.max_delay);
+        if self.jitter {
+            // Equal jitter: keep half, randomize the other half.
+            delay / 2 + delay.mul_f64(random_fraction() / 2.0)
+        } else {
+            delay
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+        }
+    }
+}
+
+pub(super) async fn send_with_retry<T: Transport>(
+    transport: &T,
+    policy: &RetryPolicy,
+    request: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let mut attempt = 1;
+    loop {
+        request.check_cancelled()?;
+        let result = transport.send(request.clone()).await;
+        let retry_after = match &result {
+            Ok(response) if is_transient_status(response.status) => response.retry_after(),
+            Err(err) if err.is_transient() => None,
+            _ => return result,
+        };
+        if attempt >= policy.max_attempts {
+            return result;
+        }
+        let delay = match retry_after {
+            Some(retry_after) if retry_after > policy.max_delay => return result,
+            Some(retry_after) => retry_after,
+            None => policy.backoff(attempt),
+        };
+        info!(
+            "attempt {} fetching {} failed, retrying in {:?}",
+            attempt, request.url, delay
+        );
+        sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+async fn sleep(duration: Duration) {
+    futures_timer::Delay::new(duration).await
+}
+
+fn random_fraction() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Math::random()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::collections::hash_map::RandomState;
+        use std::hash::{BuildHasher, Hasher};
+        // Good enough for spreading out retries without pulling in a rand crate.
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        hasher.finish() as f64 / u64::MAX as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use std::cell::RefCell;
+
+    // Replays responses in order, repeating the last one.
+    struct ScriptedTransport {
+        responses: RefCell<Vec<Result<HttpResponse, Error>>>,
+        attempts: RefCell<u32>,
+    }
+
+    impl ScriptedTransport {
+        fn new(responses: Vec<Result<HttpResponse, Error>>) -> Self {
+            Self {
+                responses: RefCell::new(responses),
+                attempts: RefCell::new(0),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Transport for ScriptedTransport {
+        async fn send(&self, _request: HttpRequest) -> Result<HttpResponse, Error> {
+            *self.attempts.borrow_mut() += 1;
+            let mut responses = self.responses.borrow_mut();
+            if responses.len() > 1 {
+                responses.remove(0)
+            } else {
+                match &responses[0] {
+                    Ok(response) => Ok(response.clone()),
+                    Err(_) => Err(Error::Network {
+                        url: "".to_owned(),
+                        message: "again".to_owned(),
+                    }),
+                }
+            }
+        }
+    }
+
+    fn status(status: u16, retry_after: Option<&str>) -> Result<HttpResponse, Error> {
+        Ok(HttpResponse {
+            status,
+            headers: retry_after
+                .map(|value| ("retry-after".to_owned(), value.to_owned()))
+                .into_iter()
+                .collect(),
+            body: vec![],
+        })
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            base_delay: Duration::ZERO,
+            jitter: false,
+            ..Default::default()
+        }
+    }
+
+    fn send(transport: &ScriptedTransport, policy: &RetryPolicy) -> Result<HttpResponse, Error> {
+        block_on(send_with_retry(transport, policy, HttpRequest::get("/x")))
+    }
+
+    #[test]
+    fn test_retries_transient_until_success() {
+        let transport = ScriptedTransport::new(vec![
+            status(503, Some("0")),
+            Err(Error::Network {
+                url: "/x".to_owned(),
+                message: "reset".to_owned(),
+            }),
+            status(200, None),
+        ]);
+        assert_eq!(send(&transport, &fast_policy()).unwrap().status, 200);
+        assert_eq!(*transport.attempts.borrow(), 3);
+    }
+
+    #[test]
+    fn test_gives_up() {
+        let transport = ScriptedTransport::new(vec![status(502, None)]);
+        assert_eq!(send(&transport, &fast_policy()).unwrap().status, 502);
+        assert_eq!(*transport.attempts.borrow(), 4);
+        let transport = ScriptedTransport::new(vec![status(404, None)]);
+        assert_eq!(send(&transport, &fast_policy()).unwrap().status, 404);
+        assert_eq!(*transport.attempts.borrow(), 1);
+    }
+
+    #[test]
+    fn test_retry_after_beyond_max_delay() {
+        let transport = ScriptedTransport::new(vec![status(429, Some("3600")), status(200, None)]);
+        assert_eq!(send(&transport, &fast_policy()).unwrap().status, 429);
+        assert_eq!(*transport.attempts.borrow(), 1);
+    }
+
+    #[test]
+    fn test_cancelled() {
+        let transport = ScriptedTransport::new(vec![status(200, None)]);
+        let cancel = crate::api::CancelToken::new();
+        cancel.cancel();
+        let request = HttpRequest::get("/x").cancel(Some(cancel));
+        let result = block_on(send_with_retry(&transport, &fast_policy(), request));
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(*transport.attempts.borrow(), 0);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(30), policy.max_delay);
+        let jittered = RetryPolicy::default().backoff(2);
+        assert!(jittered >= Duration::from_millis(500) && jittered <= Duration::from_secs(1));
+    }
+}
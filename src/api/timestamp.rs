@@ -0,0 +1,158 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Debug, Display, Formatter};
+
+// What Go's time.Time{} marshals to.
+const GO_ZERO_TIME: &str = "0001-01-01T00:00:00Z";
+
+// A time from the Go API, where the zero time (or null) means it never happened, like a torrent
+// that was never scraped. Missing times sort before everything else.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timestamp(Option<DateTime<Utc>>);
+
+impl Timestamp {
+    pub fn new(datetime: DateTime<Utc>) -> Self {
+        Self(Some(datetime))
+    }
+
+    pub fn zero() -> Self {
+        Self(None)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn datetime(&self) -> Option<DateTime<Utc>> {
+        self.0
+    }
+
+    pub fn age(&self, now: DateTime<Utc>) -> Option<chrono::Duration> {
+        self.0.map(|datetime| now - datetime)
+    }
+
+    // Like "3 days ago". Only the largest unit is shown, the absolute time is there for detail.
+    pub fn relative_to(&self, now: DateTime<Utc>) -> String {
+        let Some(age) = self.age(now) else {
+            return "never".to_owned();
+        };
+        let (ago, age) = if age < chrono::Duration::zero() {
+            (false, -age)
+        } else {
+            (true, age)
+        };
+        let seconds = age.num_seconds();
+        if seconds < 60 {
+            return "just now".to_owned();
+        }
+        const UNITS: [(i64, &str); 6] = [
+            (365 * 24 * 60 * 60, "year"),
+            (30 * 24 * 60 * 60, "month"),
+            (7 * 24 * 60 * 60, "week"),
+            (24 * 60 * 60, "day"),
+            (60 * 60, "hour"),
+            (60, "minute"),
+        ];
+        let (unit_seconds, unit) = UNITS
+            .into_iter()
+            .find(|(unit_seconds, _)| seconds >= *unit_seconds)
+            .unwrap();
+        let count = seconds / unit_seconds;
+        let plural = if count == 1 { "" } else { "s" };
+        if ago {
+            format!("{} {}{} ago", count, unit, plural)
+        } else {
+            format!("in {} {}{}", count, unit, plural)
+        }
+    }
+
+    pub fn relative(&self) -> String {
+        self.relative_to(Utc::now())
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(datetime) => f.write_str(&datetime.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            None => f.write_str("never"),
+        }
+    }
+}
+
+impl Debug for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Some(value): Option<String> = Deserialize::deserialize(deserializer)? else {
+            return Ok(Self::zero());
+        };
+        if value.is_empty() || value == GO_ZERO_TIME {
+            return Ok(Self::zero());
+        }
+        DateTime::parse_from_rfc3339(&value)
+            .map(|datetime| Self::new(datetime.with_timezone(&Utc)))
+            .map_err(|err| D::Error::custom(format!("parsing time {:?}: {}", value, err)))
+    }
+}
+
+// In the same form Go sends, so fixtures round trip.
+impl Serialize for Timestamp {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            Some(_) => serializer.serialize_str(&self.to_string()),
+            None => serializer.serialize_str(GO_ZERO_TIME),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Timestamp {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let timestamp = parse(r#""2022-11-11T01:47:42Z""#);
+        assert_eq!(timestamp.to_string(), "2022-11-11T01:47:42Z");
+        assert!(parse(r#""0001-01-01T00:00:00Z""#).is_zero());
+        assert!(parse("null").is_zero());
+        assert!(serde_json::from_str::<Timestamp>(r#""yesterday""#).is_err());
+        // Go includes fractional seconds and local offsets when it has them.
+        assert_eq!(
+            parse(r#""2023-05-08T17:05:43.123456+02:00""#).to_string(),
+            "2023-05-08T15:05:43Z"
+        );
+    }
+
+    #[test]
+    fn test_relative() {
+        let now = parse(r#""2023-05-08T15:05:43Z""#).datetime().unwrap();
+        let relative = |json| parse(json).relative_to(now);
+        assert_eq!(relative(r#""2023-05-08T15:05:13Z""#), "just now");
+        assert_eq!(relative(r#""2023-05-08T14:05:43Z""#), "1 hour ago");
+        assert_eq!(relative(r#""2023-05-05T15:05:43Z""#), "3 days ago");
+        assert_eq!(relative(r#""2022-11-11T01:47:42Z""#), "5 months ago");
+        assert_eq!(relative(r#""2023-05-10T15:05:43Z""#), "in 2 days");
+        assert_eq!(relative("null"), "never");
+    }
+
+    #[test]
+    fn test_zero_sorts_first() {
+        assert!(Timestamp::zero() < parse(r#""1970-01-01T00:00:00Z""#));
+    }
+}
@@ -0,0 +1,311 @@
+use crate::Error;
+use async_trait::async_trait;
+use std::cell::Cell;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+use std::time::Duration;
+
+// Cancels the requests it's attached to. In the browser this aborts the fetch, elsewhere it stops
+// further attempts.
+#[derive(Clone)]
+pub struct CancelToken(Rc<CancelInner>);
+
+struct CancelInner {
+    cancelled: Cell<bool>,
+    #[cfg(target_arch = "wasm32")]
+    controller: Option<web_sys::AbortController>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self(Rc::new(CancelInner {
+            cancelled: Cell::new(false),
+            #[cfg(target_arch = "wasm32")]
+            controller: web_sys::AbortController::new().ok(),
+        }))
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.set(true);
+        #[cfg(target_arch = "wasm32")]
+        if let Some(controller) = &self.0.controller {
+            controller.abort();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.get()
+    }
+
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn abort_signal(&self) -> Option<web_sys::AbortSignal> {
+        self.0
+            .controller
+            .as_ref()
+            .map(|controller| controller.signal())
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Debug for CancelToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CancelToken")
+            .field(&self.is_cancelled())
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Option<String>,
+    pub cancel: Option<CancelToken>,
+}
+
+impl HttpRequest {
+    pub fn get(url: impl Into<String>) -> Self {
+        Self {
+            method: Method::Get,
+            url: url.into(),
+            headers: vec![],
+            body: None,
+            cancel: None,
+        }
+    }
+
+    pub fn post(url: impl Into<String>, content_type: &str, body: String) -> Self {
+        Self {
+            method: Method::Post,
+            body: Some(body),
+            ..Self::get(url)
+        }
+        .header("Content-Type", content_type)
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn cancel(mut self, cancel: Option<CancelToken>) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn check_cancelled(&self) -> Result<(), Error> {
+        self.cancel
+            .as_ref()
+            .map(CancelToken::check)
+            .unwrap_or(Ok(()))
+    }
+}
+
+// The whole body is read up front. The indexer responses are JSON documents that we need in full
+// before decoding anyway.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct HttpResponse {
+    pub status: u16,
+    // Names are lowercase.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Only the delay-seconds form. Servers sending an HTTP date get our own backoff instead.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.header("retry-after")?
+            .trim()
+            .parse()
+            .ok()
+            .map(Duration::from_secs)
+    }
+}
+
+// The futures aren't Send because the browser fetch API isn't.
+#[async_trait(?Send)]
+pub trait Transport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, Error>;
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GlooTransport;
+
+#[async_trait(?Send)]
+impl Transport for GlooTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        request.check_cancelled()?;
+        let mut builder = match request.method {
+            Method::Get => gloo_net::http::Request::get(&request.url),
+            Method::Post => gloo_net::http::Request::post(&request.url),
+        };
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = &request.body {
+            builder = builder.body(body.as_str());
+        }
+        #[cfg(target_arch = "wasm32")]
+        let abort_signal = request.cancel.as_ref().and_then(CancelToken::abort_signal);
+        #[cfg(target_arch = "wasm32")]
+        let builder = builder.abort_signal(abort_signal.as_ref());
+        // An aborted fetch fails like any other, so check whether that's what happened first.
+        let fetch_failure = |err: gloo_net::Error| match request.check_cancelled() {
+            Ok(()) => classify_fetch_failure(&request.url, err),
+            Err(cancelled) => cancelled,
+        };
+        let response = builder.send().await.map_err(fetch_failure)?;
+        Ok(HttpResponse {
+            status: response.status(),
+            headers: response
+                .headers()
+                .entries()
+                .map(|(name, value)| (name.to_lowercase(), value))
+                .collect(),
+            body: response.binary().await.map_err(fetch_failure)?,
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport(reqwest::Client);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self(client)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait(?Send)]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        request.check_cancelled()?;
+        let method = match request.method {
+            Method::Get => reqwest::Method::GET,
+            Method::Post => reqwest::Method::POST,
+        };
+        let mut builder = self.0.request(method, &request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(*name, value);
+        }
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+        let network = |err: reqwest::Error| Error::Network {
+            url: request.url.clone(),
+            message: err.to_string(),
+        };
+        let response = builder.send().await.map_err(network)?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect();
+        let body = response.bytes().await.map_err(network)?.to_vec();
+        request.check_cancelled()?;
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+fn classify_fetch_failure(url: &str, err: gloo_net::Error) -> Error {
+    let page = web_sys::window().and_then(|window| window.location().href().ok());
+    classify_failure(page.as_deref(), url, err.to_string())
+}
+
+fn classify_failure(page: Option<&str>, url: &str, message: String) -> Error {
+    let url = url.to_owned();
+    let Some(page) = page.and_then(|page| url::Url::parse(page).ok()) else {
+        return Error::Network { url, message };
+    };
+    let Ok(target) = page.join(&url) else {
+        return Error::Network { url, message };
+    };
+    if page.scheme() == "https" && target.scheme() == "http" {
+        Error::MixedContent { url }
+    } else if page.origin() != target.origin() {
+        Error::CorsSuspected { url, message }
+    } else {
+        Error::Network { url, message }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub type DefaultTransport = GlooTransport;
+#[cfg(not(target_arch = "wasm32"))]
+pub type DefaultTransport = ReqwestTransport;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(page: &str, url: &str) -> Error {
+        classify_failure(Some(page), url, "TypeError: Failed to fetch".to_owned())
+    }
+
+    #[test]
+    fn test_classify_failure() {
+        assert!(matches!(
+            classify(
+                "https://wasm.dht.lol/",
+                "http://localhost:8080/localDhtIndexer/searchInfos"
+            ),
+            Error::MixedContent { .. }
+        ));
+        assert!(matches!(
+            classify(
+                "http://localhost:8081/",
+                "http://localhost:8080/localDhtIndexer/searchInfos"
+            ),
+            Error::CorsSuspected { .. }
+        ));
+        assert!(matches!(
+            classify("https://wasm.dht.lol/", "/dhtindex/searchInfos"),
+            Error::Network { .. }
+        ));
+    }
+}
@@ -0,0 +1,84 @@
+// Serves the fake dht-indexer over HTTP, so the app can be run against it with
+// `?indexer=http://localhost:8082/`.
+//
+// cargo run --features fake-indexer --bin fake_indexer -- [--port 8082] [--fixture FILE]...
+//     [--http-error STATUS] [--partial N] [--missing INFOHASH]... [--delay-ms MS] [--malformed]
+//     [--reject-post]
+
+use anyhow::{bail, Context};
+use dhtsearch::api::Method;
+use dhtsearch::fake_indexer::FakeIndexer;
+use std::io::Read;
+use std::time::Duration;
+
+fn main() -> anyhow::Result<()> {
+    let mut fake = FakeIndexer::with_repo_fixtures()?;
+    let mut port = 8082;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .with_context(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--port" => port = value()?.parse()?,
+            "--fixture" => {
+                let path = value()?;
+                let json = std::fs::read_to_string(&path).with_context(|| path.clone())?;
+                fake.load_info_files_json(&json).with_context(|| path)?;
+            }
+            "--http-error" => {
+                fake.faults.http_error = Some((value()?.parse()?, "injected error".to_owned()))
+            }
+            "--partial" => fake.faults.partial = Some(value()?.parse()?),
+            "--missing" => {
+                fake.faults.missing.insert(value()?.to_lowercase());
+            }
+            "--delay-ms" => fake.faults.delay = Some(Duration::from_millis(value()?.parse()?)),
+            "--malformed" => fake.faults.malformed_json = true,
+            "--reject-post" => fake.faults.reject_post = true,
+            _ => bail!("unknown argument {:?}", arg),
+        }
+    }
+    let server =
+        tiny_http::Server::http(("127.0.0.1", port)).map_err(|err| anyhow::anyhow!(err))?;
+    eprintln!("fake indexer listening on http://127.0.0.1:{}/", port);
+    for mut request in server.incoming_requests() {
+        if let Some(delay) = fake.faults.delay {
+            std::thread::sleep(delay);
+        }
+        let method = match request.method() {
+            tiny_http::Method::Post => Method::Post,
+            _ => Method::Get,
+        };
+        let mut body = String::new();
+        if method == Method::Post {
+            request.as_reader().read_to_string(&mut body)?;
+        }
+        let response = fake.respond_to(method, request.url(), Some(&body));
+        eprintln!(
+            "{} {} -> {}",
+            request.method(),
+            request.url(),
+            response.status
+        );
+        let content_type = if response.ok() {
+            "application/json"
+        } else {
+            "text/plain; charset=utf-8"
+        };
+        let response = tiny_http::Response::from_data(response.body)
+            .with_status_code(response.status)
+            .with_header(header("Content-Type", content_type))
+            // The app is served from a different port.
+            .with_header(header("Access-Control-Allow-Origin", "*"));
+        if let Err(err) = request.respond(response) {
+            eprintln!("error responding: {}", err);
+        }
+    }
+    Ok(())
+}
+
+fn header(name: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(name, value).unwrap()
+}
@@ -0,0 +1,338 @@
+// A stand-in for the dht-indexer HTTP API, serving searchInfos and infoFiles from fixtures in the
+// same JSON shape the Go server produces. It can be used in-process as a Transport for tests, or
+// over HTTP with the fake_indexer binary (see the fake-indexer feature).
+
+use crate::api::{
+    HttpRequest, HttpResponse, InfoFiles, InfoHash, Method, Transport, COMPRESSION_QUERY_PARAM,
+};
+use crate::Error;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default)]
+pub struct Faults {
+    // Respond to everything with this status and body.
+    pub http_error: Option<(u16, String)>,
+    // Only return up to this many of the requested info files.
+    pub partial: Option<usize>,
+    // Info hashes that are never included in infoFiles responses.
+    pub missing: HashSet<InfoHash>,
+    // Only applied when serving over HTTP, there's no executor-agnostic timer for in-process use.
+    pub delay: Option<Duration>,
+    // Cut successful bodies off halfway.
+    pub malformed_json: bool,
+    // Respond 405 to POSTs, like an indexer that only knows GET.
+    pub reject_post: bool,
+}
+
+#[derive(Default)]
+pub struct FakeIndexer {
+    // In the Go InfoFiles shape.
+    info_files: HashMap<InfoHash, Value>,
+    // Explicit searchInfos responses by query. Other queries are answered from info_files.
+    searches: HashMap<String, Value>,
+    pub faults: Faults,
+    requests: RefCell<Vec<String>>,
+}
+
+impl FakeIndexer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Loads every fixture checked into the repo.
+    pub fn with_repo_fixtures() -> serde_json::Result<Self> {
+        let mut this = Self::new();
+        this.load_info_files_json(include_str!(
+            "../testdata/40f3761b9080949ca6ffed3522ad872bc0bef41b.infoFiles.json"
+        ))?;
+        this.load_info_files_json(include_str!(
+            "../benches/3670d38c31d660d690384731483e145695586797.infoFiles.json"
+        ))?;
+        Ok(this)
+    }
+
+    // Takes an infoFiles response body, as saved from a real indexer.
+    pub fn load_info_files_json(&mut self, json: &str) -> serde_json::Result<()> {
+        let payload: Vec<Value> = serde_json::from_str(json)?;
+        for info_files in payload {
+            // Check it decodes, so broken fixtures are caught at load rather than in the client.
+            let parsed: InfoFiles = serde_json::from_value(info_files.clone())?;
+            self.info_files.insert(parsed.info.info_hash, info_files);
+        }
+        Ok(())
+    }
+
+    pub fn add_search(&mut self, query: impl Into<String>, response: Value) {
+        self.searches.insert(query.into(), response);
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.borrow().clone()
+    }
+
+    // Form bodies are treated like query parameters, as Go's ParseForm does.
+    pub fn respond_to(&self, method: Method, url: &str, body: Option<&str>) -> HttpResponse {
+        match (method, body) {
+            (Method::Post, _) if self.faults.reject_post => {
+                self.requests.borrow_mut().push(url.to_owned());
+                text_response(405, "Method Not Allowed")
+            }
+            (Method::Post, Some(body)) => {
+                let separator = if url.contains('?') { '&' } else { '?' };
+                self.respond(&format!("{}{}{}", url, separator, body))
+            }
+            _ => self.respond(url),
+        }
+    }
+
+    // Handles a request URL, which may be absolute or just the path and query.
+    pub fn respond(&self, url: &str) -> HttpResponse {
+        self.requests.borrow_mut().push(url.to_owned());
+        if let Some((status, body)) = &self.faults.http_error {
+            return HttpResponse {
+                status: *status,
+                body: body.clone().into_bytes(),
+                ..Default::default()
+            };
+        }
+        let base = url::Url::parse("http://fake-indexer/").unwrap();
+        let url = match base.join(url) {
+            Ok(url) => url,
+            Err(err) => return text_response(400, &err.to_string()),
+        };
+        let value = match url.path().rsplit('/').next() {
+            Some("searchInfos") => {
+                let query = query_values(&url, "s").next().unwrap_or_default();
+                let offset = query_values(&url, "offset")
+                    .next()
+                    .and_then(|v| v.parse().ok());
+                let limit = query_values(&url, "limit")
+                    .next()
+                    .and_then(|v| v.parse().ok());
+                self.search(&query, offset.unwrap_or(0), limit.unwrap_or(usize::MAX))
+            }
+            Some("infoFiles") => self.info_files_payload(query_values(&url, "ih")),
+            _ => return text_response(404, "404 page not found"),
+        };
+        let mut body = serde_json::to_vec(&value).unwrap();
+        // zstd is requestable but there's no pure Rust encoder handy, so it gets the plain JSON
+        // fallback, as would a server that doesn't support it.
+        if query_values(&url, COMPRESSION_QUERY_PARAM).any(|encoding| encoding == "lz4") {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(vec![]);
+            encoder.write_all(&body).unwrap();
+            body = encoder.finish().unwrap();
+        }
+        if self.faults.malformed_json {
+            body.truncate(body.len() / 2);
+        }
+        HttpResponse {
+            status: 200,
+            body,
+            ..Default::default()
+        }
+    }
+
+    fn search(&self, query: &str, offset: usize, limit: usize) -> Value {
+        if let Some(response) = self.searches.get(query) {
+            return response.clone();
+        }
+        let query = query.to_lowercase();
+        let mut matches: Vec<InfoFiles> = self
+            .info_files
+            .values()
+            .filter_map(|value| serde_json::from_value::<InfoFiles>(value.clone()).ok())
+            .filter(|info_files| {
+                info_files
+                    .info
+                    .name
+                    .to_string()
+                    .to_lowercase()
+                    .contains(&query)
+            })
+            .collect();
+        // HashMap order isn't stable, and pages need to be.
+        matches.sort_by(|left, right| left.info.info_hash.cmp(&right.info.info_hash));
+        let items: Vec<Value> = matches
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(search_item)
+            .collect();
+        json!({
+            "Total": matches.len(),
+            "Err": null,
+            "Items": items,
+        })
+    }
+
+    fn info_files_payload(&self, info_hashes: impl Iterator<Item = String>) -> Value {
+        // Unparseable info hashes are never found, the same as unknown ones.
+        let found = info_hashes
+            .filter_map(|ih| ih.parse::<InfoHash>().ok())
+            .filter(|ih| !self.faults.missing.contains(ih))
+            .filter_map(|ih| self.info_files.get(&ih).cloned())
+            .take(self.faults.partial.unwrap_or(usize::MAX));
+        Value::Array(found.collect())
+    }
+}
+
+#[async_trait(?Send)]
+impl Transport for FakeIndexer {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        request.check_cancelled()?;
+        Ok(self.respond_to(request.method, &request.url, request.body.as_deref()))
+    }
+}
+
+fn query_values<'a>(url: &'a url::Url, key: &'a str) -> impl Iterator<Item = String> + 'a {
+    url.query_pairs()
+        .filter(move |(k, _)| k == key)
+        .map(|(_, v)| v.into_owned())
+}
+
+fn text_response(status: u16, body: &str) -> HttpResponse {
+    HttpResponse {
+        status,
+        body: body.as_bytes().to_vec(),
+        ..Default::default()
+    }
+}
+
+fn search_item(info_files: &InfoFiles) -> Value {
+    let info = &info_files.info;
+    json!({
+        "InfoHash": info.info_hash,
+        "InfoHashV2": info.info_hash_v2,
+        // Unlike infoFiles, search results send the name as a plain string.
+        "Name": info.name.to_string(),
+        "SwarmInfo": {
+            "Seeders": info.scrape_data.seeders,
+            "Completed": info.scrape_data.completed,
+            "Leechers": info.scrape_data.leechers,
+        },
+        "Size": info_files.files().iter().map(|file| file.length).sum::<i64>(),
+        "Age": info.age,
+        "NoSwarmInfo": false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        Compression, Indexer, IndexerClient, IndexerEndpoint, InfoFilesBatching, RetryPolicy,
+        SearchPage,
+    };
+    use futures::executor::block_on;
+
+    const AARON_SWARTZ: &str = "40f3761b9080949ca6ffed3522ad872bc0bef41b";
+    const VAULT: &str = "3670d38c31d660d690384731483e145695586797";
+
+    fn indexer(fake: FakeIndexer) -> Indexer<FakeIndexer> {
+        Indexer::with_transport(IndexerEndpoint::new("/dhtindex/"), fake)
+            .with_retry(RetryPolicy::none())
+    }
+
+    fn ih(s: &str) -> InfoHash {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_search_fixtures() {
+        let indexer = indexer(FakeIndexer::with_repo_fixtures().unwrap());
+        let result = block_on(indexer.search("swartz".to_owned(), SearchPage::first())).unwrap();
+        assert_eq!(result.total, 1);
+        assert_eq!(result.items[0].info_hash, ih(AARON_SWARTZ));
+        assert_eq!(result.items[0].size, 1701564468);
+    }
+
+    #[test]
+    fn test_search_pages() {
+        let indexer = indexer(FakeIndexer::with_repo_fixtures().unwrap());
+        let page = SearchPage {
+            offset: 1,
+            limit: 1,
+        };
+        let result = block_on(indexer.search("".to_owned(), page)).unwrap();
+        assert_eq!(result.total, 2);
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(result.items[0].info_hash, ih(AARON_SWARTZ));
+    }
+
+    #[test]
+    fn test_info_files_missing() {
+        let mut fake = FakeIndexer::with_repo_fixtures().unwrap();
+        fake.faults.missing.insert(ih(VAULT));
+        let indexer = indexer(fake);
+        let payload = block_on(indexer.get_info_files(&[ih(AARON_SWARTZ), ih(VAULT)])).unwrap();
+        assert_eq!(payload.len(), 1);
+        assert_eq!(payload[0].info.info_hash, ih(AARON_SWARTZ));
+    }
+
+    #[test]
+    fn test_info_files_partial() {
+        let mut fake = FakeIndexer::with_repo_fixtures().unwrap();
+        fake.faults.partial = Some(1);
+        let payload =
+            block_on(indexer(fake).get_info_files(&[ih(AARON_SWARTZ), ih(VAULT)])).unwrap();
+        assert_eq!(payload.len(), 1);
+    }
+
+    #[test]
+    fn test_faults() {
+        let mut fake = FakeIndexer::with_repo_fixtures().unwrap();
+        fake.faults.malformed_json = true;
+        let err = block_on(indexer(fake).get_info_files(&[ih(VAULT)])).unwrap_err();
+        assert!(matches!(&**err, Error::Decode { .. }));
+        let mut fake = FakeIndexer::new();
+        fake.faults.http_error = Some((503, "overloaded".to_owned()));
+        let err = block_on(indexer(fake).get_info_files(&[ih(VAULT)])).unwrap_err();
+        assert!(matches!(
+            &**err,
+            Error::HttpStatus { status: 503, body } if body == "overloaded"
+        ));
+    }
+
+    #[test]
+    fn test_info_files_post_with_fallback() {
+        let batching = InfoFilesBatching {
+            post: true,
+            ..Default::default()
+        };
+        let info_hashes = [ih(AARON_SWARTZ), ih(VAULT)];
+        let posting =
+            indexer(FakeIndexer::with_repo_fixtures().unwrap()).with_batching(batching.clone());
+        assert_eq!(
+            block_on(posting.get_info_files(&info_hashes))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(posting.transport().requests().len(), 1);
+        let mut fake = FakeIndexer::with_repo_fixtures().unwrap();
+        fake.faults.reject_post = true;
+        let falling_back = indexer(fake).with_batching(batching);
+        assert_eq!(
+            block_on(falling_back.get_info_files(&info_hashes))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(falling_back.transport().requests().len(), 2);
+    }
+
+    #[test]
+    fn test_info_files_compression() {
+        for compression in [Compression::Lz4, Compression::Zstd] {
+            let indexer =
+                indexer(FakeIndexer::with_repo_fixtures().unwrap()).with_compression(compression);
+            let payload = block_on(indexer.get_info_files(&[ih(VAULT)])).unwrap();
+            assert_eq!(payload[0].files.len(), 15682);
+        }
+    }
+}
@@ -1,12 +1,12 @@
 use super::*;
-use crate::api::UpvertedFile;
-use std::cmp::Ordering;
-use std::collections::HashSet;
+use crate::api::{DecodedBytes, UpvertedFile};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 
 #[derive(Eq, Debug, Ord, PartialOrd, Clone)]
 pub struct FileRow {
-    pub path: Vec<String>,
+    pub path: Vec<DecodedBytes>,
     pub dir: bool,
     // Later I will show the total size of a directory.
     pub size: Option<i64>,
@@ -22,18 +22,12 @@ impl Hash for FileRow {
 }
 
 impl FileRow {
-    pub fn leaf(&self) -> Option<&String> {
+    pub fn leaf(&self) -> Option<&DecodedBytes> {
         self.path.last()
     }
 
     pub fn iter_path(&self) -> impl Iterator<Item = &str> {
-        self.path.iter().map(|x| x.as_str())
-    }
-
-    fn compare_with_collator(&self, other: &Self, collator: &Collator) -> Ordering {
-        self.iter_path().cmp_by(other.iter_path(), |left, right| {
-            collator.compare(left, right)
-        })
+        self.path.iter().map(DecodedBytes::as_str)
     }
 }
 
@@ -70,24 +64,150 @@ fn dir_file_rows(files: &[UpvertedFile]) -> Vec<FileRow> {
     files
         .iter()
         .flat_map(file_dir_file_rows)
-        .collect::<HashSet<_>>()
+        .collect::<BTreeSet<_>>()
         .into_iter()
         .collect()
 }
 
+// Directories by path, then files in torrent order. The order is the same every time, so a memo
+// of these doesn't change when the cache they came from does. FileRowTree sorts them for display.
 pub fn info_files_to_file_rows(upverted: &[UpvertedFile]) -> Vec<FileRow> {
     let mut rows = dir_file_rows(upverted);
     rows.extend(file_rows(upverted));
-    let collator = new_collator();
-    rows.sort_by(|left, right| left.compare_with_collator(right, &collator));
     rows
 }
 
+// Each distinct name collated once, so sorts compare integers instead of running the collator on
+// the same strings over and over. Names that collate equal share a rank.
+struct NameRanks<'a>(HashMap<&'a str, usize>);
+
+impl<'a> NameRanks<'a> {
+    fn new(names: impl IntoIterator<Item = &'a str>, collator: &Collator) -> Self {
+        let mut names: Vec<&str> = names
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        names.sort_unstable_by(|left, right| collator.compare(left, right));
+        let mut ranks = HashMap::with_capacity(names.len());
+        let mut rank = 0;
+        for (index, name) in names.iter().enumerate() {
+            if index > 0 && collator.compare(names[index - 1], name) != Ordering::Equal {
+                rank += 1;
+            }
+            ranks.insert(*name, rank);
+        }
+        Self(ranks)
+    }
+
+    fn rank(&self, name: &str) -> usize {
+        self.0[name]
+    }
+}
+
+pub struct FileRowNode<'a> {
+    pub name: &'a str,
+    pub depth: usize,
+    pub so: Option<usize>,
+    // Including everything underneath.
+    pub size: u64,
+    pub children: Vec<usize>,
+    parent: usize,
+}
+
+// File rows as a tree, built in one pass and sorted for display. Nodes live in a single Vec and
+// refer to each other by index, and parents always come before their children.
+pub struct FileRowTree<'a> {
+    nodes: Vec<FileRowNode<'a>>,
+}
+
+impl<'a> FileRowTree<'a> {
+    pub const ROOT: usize = 0;
+
+    // The rows can be in any order. Directories without rows of their own are added as needed.
+    pub fn new(file_rows: impl IntoIterator<Item = &'a FileRow>) -> Self {
+        let mut tree = Self {
+            nodes: vec![FileRowNode {
+                name: "",
+                depth: 0,
+                so: None,
+                size: 0,
+                children: vec![],
+                parent: Self::ROOT,
+            }],
+        };
+        let mut dirs: HashMap<(usize, &DecodedBytes), usize> = HashMap::new();
+        for file_row in file_rows {
+            let Some((leaf, dir_path)) = file_row.path.split_last() else {
+                continue;
+            };
+            let mut parent = Self::ROOT;
+            for part in dir_path {
+                parent = *dirs
+                    .entry((parent, part))
+                    .or_insert_with(|| tree.push(parent, part.as_str(), None, 0));
+            }
+            if file_row.dir {
+                dirs.entry((parent, leaf))
+                    .or_insert_with(|| tree.push(parent, leaf.as_str(), None, 0));
+            } else {
+                let size = file_row.size.unwrap_or_default() as u64;
+                tree.push(parent, leaf.as_str(), file_row.so, size);
+            }
+        }
+        // Going backwards, every node is done before its size is added to its parent.
+        for index in (1..tree.nodes.len()).rev() {
+            let node = &tree.nodes[index];
+            let (parent, size) = (node.parent, node.size);
+            tree.nodes[parent].size += size;
+        }
+        tree.sort_children(collator());
+        tree
+    }
+
+    fn push(&mut self, parent: usize, name: &'a str, so: Option<usize>, size: u64) -> usize {
+        let index = self.nodes.len();
+        let depth = self.nodes[parent].depth + 1;
+        self.nodes[parent].children.push(index);
+        self.nodes.push(FileRowNode {
+            name,
+            depth,
+            so,
+            size,
+            children: vec![],
+            parent,
+        });
+        index
+    }
+
+    // By name, then files before directories of the same name, then largest first.
+    fn sort_children(&mut self, collator: &Collator) {
+        let ranks = NameRanks::new(self.nodes.iter().map(|node| node.name), collator);
+        let keys: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    ranks.rank(node.name),
+                    !node.children.is_empty(),
+                    Reverse(node.size),
+                )
+            })
+            .collect();
+        for node in &mut self.nodes {
+            node.children.sort_by_key(|&child| keys[child]);
+        }
+    }
+
+    pub fn node(&self, index: usize) -> &FileRowNode<'a> {
+        &self.nodes[index]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::api::*;
-    use crate::leptos::FileView;
     use pretty_assertions::assert_eq;
     use std::iter::once;
 
@@ -97,7 +217,7 @@ mod tests {
                 .iter()
                 .cloned()
                 .chain(once(leaf))
-                .map(|s| s.to_string())
+                .map(Into::into)
                 .collect(),
             dir: true,
             size: None,
@@ -114,8 +234,20 @@ mod tests {
         assert_eq!(got, expected);
     }
 
+    // Depth, name, size and file index of each node, parents before children.
+    fn pre_order<'a>(tree: &FileRowTree<'a>) -> Vec<(usize, &'a str, u64, Option<usize>)> {
+        let mut nodes = vec![];
+        let mut stack = vec![FileRowTree::ROOT];
+        while let Some(index) = stack.pop() {
+            let node = tree.node(index);
+            nodes.push((node.depth, node.name, node.size, node.so));
+            stack.extend(node.children.iter().rev());
+        }
+        nodes
+    }
+
     #[test]
-    fn test_simple_file_rows_and_views() {
+    fn test_simple_file_rows_and_tree() {
         let upverted = InfoFiles {
             info: Info {
                 name: "a".to_owned().into(),
@@ -126,7 +258,7 @@ mod tests {
                     path: Some(
                         vec!["a", "b", "c", "10"]
                             .into_iter()
-                            .map(ToOwned::to_owned)
+                            .map(Into::into)
                             .collect(),
                     ),
                     length: 1,
@@ -135,7 +267,7 @@ mod tests {
                     path: Some(
                         vec!["a", "b", "c", "10"]
                             .into_iter()
-                            .map(ToOwned::to_owned)
+                            .map(Into::into)
                             .collect(),
                     ),
                     length: 2,
@@ -144,13 +276,13 @@ mod tests {
                     path: Some(
                         vec!["a", "b", "c", "2"]
                             .into_iter()
-                            .map(ToOwned::to_owned)
+                            .map(Into::into)
                             .collect(),
                     ),
                     length: 3,
                 },
                 File {
-                    path: Some(vec!["a", "b"].into_iter().map(ToOwned::to_owned).collect()),
+                    path: Some(vec!["a", "b"].into_iter().map(Into::into).collect()),
                     length: 4,
                 },
             ],
@@ -164,110 +296,85 @@ mod tests {
                 dir_file_row("b", &["a"]),
                 dir_file_row("c", &["a", "b"]),
                 FileRow {
-                    path: ["a", "b", "c", "10"]
-                        .into_iter()
-                        .map(ToOwned::to_owned)
-                        .collect(),
+                    path: ["a", "b", "c", "10"].into_iter().map(Into::into).collect(),
                     dir: false,
                     size: Some(1),
                     so: Some(0),
                 },
                 FileRow {
-                    path: ["a", "b", "c", "10"]
-                        .into_iter()
-                        .map(ToOwned::to_owned)
-                        .collect(),
+                    path: ["a", "b", "c", "10"].into_iter().map(Into::into).collect(),
                     dir: false,
                     size: Some(2),
                     so: Some(1),
                 },
                 FileRow {
-                    path: ["a", "b", "c", "2"]
-                        .into_iter()
-                        .map(ToOwned::to_owned)
-                        .collect(),
+                    path: ["a", "b", "c", "2"].into_iter().map(Into::into).collect(),
                     dir: false,
                     size: Some(3),
                     so: Some(2),
                 },
                 FileRow {
-                    path: ["a", "b"].into_iter().map(ToOwned::to_owned).collect(),
+                    path: ["a", "b"].into_iter().map(Into::into).collect(),
                     dir: false,
                     size: Some(4),
                     so: Some(3),
                 },
             ],
         );
-        let file_view = FileView::from_file_rows(&file_rows);
         assert_eq!(
-            file_view,
-            FileView {
-                depth: 0,
-                expanded: false,
-                name: "".to_string(),
-                size: 10,
-                so: None,
-                children: vec![FileView {
-                    depth: 1,
-                    expanded: false,
-                    name: "a".to_string(),
-                    size: 10,
-                    so: None,
-                    children: vec![
-                        FileView {
-                            depth: 2,
-                            expanded: false,
-                            name: "b".to_string(),
-                            size: 4,
-                            so: Some(3),
-                            children: vec![],
-                        },
-                        FileView {
-                            depth: 2,
-                            expanded: false,
-                            name: "b".to_string(),
-                            size: 6,
-                            so: None,
-                            children: vec![FileView {
-                                depth: 3,
-                                expanded: false,
-                                name: "c".to_string(),
-                                size: 6,
-                                so: None,
-                                children: vec![
-                                    FileView {
-                                        depth: 4,
-                                        expanded: false,
-                                        name: "2".to_string(),
-                                        size: 3,
-                                        so: Some(2),
-                                        children: vec![],
-                                    },
-                                    FileView {
-                                        depth: 4,
-                                        expanded: false,
-                                        name: "10".to_string(),
-                                        size: 2,
-                                        so: Some(1),
-                                        children: vec![],
-                                    },
-                                    FileView {
-                                        depth: 4,
-                                        expanded: false,
-                                        name: "10".to_string(),
-                                        size: 1,
-                                        so: Some(0),
-                                        children: vec![],
-                                    }
-                                ],
-                            }],
-                        }
-                    ],
-                },]
-            }
+            pre_order(&FileRowTree::new(&file_rows)),
+            [
+                (0, "", 10, None),
+                (1, "a", 10, None),
+                (2, "b", 4, Some(3)),
+                (2, "b", 6, None),
+                (3, "c", 6, None),
+                (4, "2", 3, Some(2)),
+                (4, "10", 2, Some(1)),
+                (4, "10", 1, Some(0)),
+            ]
         )
     }
 
+    #[test]
+    fn test_file_tree_row_order() {
+        let file_rows =
+            info_files_to_file_rows(&[(&["b", "2"][..], 1), (&["b", "10"], 2), (&["a"], 3)].map(
+                |(path, length)| UpvertedFile {
+                    path: path.iter().map(|&part| part.into()).collect(),
+                    length,
+                },
+            ));
+        let tree = FileRowTree::new(&file_rows);
+        // Directory rows aren't needed, and the rows can come in any order.
+        let files_only: Vec<_> = file_rows.iter().rev().filter(|row| !row.dir).collect();
+        assert_eq!(pre_order(&FileRowTree::new(files_only)), pre_order(&tree));
+        let root = tree.node(FileRowTree::ROOT);
+        assert_eq!(root.size, 6);
+        let names = |node: &FileRowNode| -> Vec<_> {
+            node.children
+                .iter()
+                .map(|&child| tree.node(child).name)
+                .collect()
+        };
+        assert_eq!(names(root), ["a", "b"]);
+        assert_eq!(names(tree.node(root.children[1])), ["2", "10"]);
+    }
+
+    #[test]
+    fn test_file_rows_stable() {
+        let upverted: Vec<_> = (0..100)
+            .map(|index| UpvertedFile {
+                path: vec![format!("dir {}", index % 50).into(), "file".into()],
+                length: index,
+            })
+            .collect();
+        assert_eq!(
+            info_files_to_file_rows(&upverted),
+            info_files_to_file_rows(&upverted)
+        );
+    }
+
     #[test]
     fn test_single_file_torrent_file_rows() {
         assert_eq!(
@@ -285,7 +392,7 @@ mod tests {
                 .upverted_files()
             ),
             vec![FileRow {
-                path: vec!["a".to_string()],
+                path: vec!["a".into()],
                 dir: false,
                 size: Some(0),
                 so: Some(0),
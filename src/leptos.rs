@@ -1,48 +1,201 @@
 use super::*;
 use crate::api::*;
+use crate::magnet::{Magnet, TrackerList};
 use ::leptos::*;
 use anyhow::anyhow;
 use filerow::FileRow;
+use futures::StreamExt;
 use leptos_router::*;
+use log::warn;
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::path::Path;
+use std::rc::Rc;
 
 mod components;
+mod export;
+mod facets;
+mod import;
+mod info_files_store;
+mod refresh;
+mod settings;
+mod sort;
+mod virtual_tree;
 
 use components::App;
 
-type SearchResultResource = Resource<String, Result<Option<InfosSearch>>>;
-type InfoFilesCache = HashMap<String, Option<Result<InfoFiles>>>;
+type SearchResultResource = Resource<(String, Indexer), Result<Option<InfosSearch>>>;
+type InfoFilesCache = HashMap<InfoHash, Option<Result<InfoFiles>>>;
+// The announce URLs from imported .torrent files, which the indexer doesn't have.
+type ImportedTrackers = HashMap<InfoHash, Vec<String>>;
+
+// The first page comes from the search resource, later pages are appended by "load more".
+#[derive(Clone, PartialEq, Default)]
+struct SearchResults {
+    total: usize,
+    items: Vec<InfoItem>,
+    // Items received before deduplication, this is the offset for the next page.
+    fetched: usize,
+    // The server returned an empty page, so stop asking regardless of what total says.
+    exhausted: bool,
+}
+
+impl SearchResults {
+    fn from_pages<'a>(pages: impl IntoIterator<Item = &'a InfosSearch>) -> Self {
+        let mut this = Self::default();
+        let mut seen = HashSet::new();
+        for page in pages {
+            this.total = page.total;
+            this.fetched += page.items.len();
+            this.exhausted = page.items.is_empty();
+            for item in &page.items {
+                if seen.insert(item.info_hash) {
+                    this.items.push(item.clone());
+                }
+            }
+        }
+        this
+    }
+
+    fn has_more(&self) -> bool {
+        !self.exhausted && self.fetched < self.total
+    }
+
+    fn next_page(&self) -> SearchPage {
+        SearchPage::at_offset(self.fetched)
+    }
+}
+
+struct InFlightFetch {
+    info_hashes: Vec<InfoHash>,
+    cancel: CancelToken,
+}
 
 fn get_needed_info_hashes(
-    cx: Scope,
-    torrent_ih: Option<String>,
-    search_result: SearchResultResource,
-) -> Vec<String> {
-    search_result
-        .read(cx)
-        .and_then(Result::ok)
-        .flatten()
-        .unwrap_or_default()
-        .items
-        .into_iter()
-        .map(|item| item.info_hash)
-        .chain(torrent_ih)
-        .collect()
+    torrent_ih: Option<InfoHash>,
+    search_results: Memo<Option<SearchResults>>,
+) -> HashSet<InfoHash> {
+    search_results.with(|results| {
+        results
+            .iter()
+            .flat_map(|results| &results.items)
+            .map(|item| item.info_hash)
+            .chain(torrent_ih)
+            .collect()
+    })
 }
 
-fn get_missing_info_hashes(cache: &InfoFilesCache, mut needed: Vec<String>) -> Vec<String> {
-    needed.retain(|ih| !cache.contains_key(ih));
+fn get_missing_info_hashes(cache: &InfoFilesCache, needed: &HashSet<InfoHash>) -> Vec<InfoHash> {
     needed
+        .iter()
+        .filter(|ih| !cache.contains_key(*ih))
+        .cloned()
+        .collect()
+}
+
+// Torrent pages are at /torrent/<info hash>, with the search's query string kept so going back
+// doesn't have to search again.
+fn torrent_path(info_hash: &InfoHash) -> String {
+    format!("/torrent/{}", info_hash)
+}
+
+// The torrent's own trackers go before the user's, without repeating any.
+fn torrent_magnet(info: &Info, own_trackers: &[String], trackers: &TrackerList) -> Magnet {
+    let mut all_trackers = own_trackers.to_vec();
+    for tracker in &trackers.0 {
+        if !all_trackers.contains(tracker) {
+            all_trackers.push(tracker.clone());
+        }
+    }
+    Magnet::new(info.info_hashes())
+        .with_display_name(info.name.to_string())
+        .with_trackers(all_trackers)
+}
+
+fn torrent_path_info_hash(path: &str) -> Option<InfoHash> {
+    path.strip_prefix("/torrent/")?
+        .trim_end_matches('/')
+        .parse()
+        .ok()
+}
+
+// The query string with one parameter set or removed, keeping the rest, like the indexer and sort
+// order. Returns "" or "?...", ready to append to a path.
+fn with_query_param(search: &str, key: &str, value: Option<&str>) -> String {
+    let search = search.strip_prefix('?').unwrap_or(search);
+    let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+    serializer.extend_pairs(
+        url::form_urlencoded::parse(search.as_bytes()).filter(|(name, _)| name != key),
+    );
+    serializer.extend_pairs(value.map(|value| (key, value)));
+    query_string(&serializer.finish())
 }
 
+// The router gives the query string without the '?'.
+fn query_string(search: &str) -> String {
+    let search = search.strip_prefix('?').unwrap_or(search);
+    if search.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", search)
+    }
+}
+
+// Replacing doesn't leave a history entry, or scroll, for things like changing the sort order.
+fn navigate_to(cx: Scope, href: &str, replace: bool) {
+    let options = NavigateOptions {
+        replace,
+        scroll: !replace,
+        ..Default::default()
+    };
+    if let Err(err) = use_navigate(cx)(href, options) {
+        warn!("navigating to {}: {:?}", href, err);
+    }
+}
+
+// Each chunk is merged into the cache as it arrives, so one slow torrent doesn't hold up the rest.
+// Anything in the store is used from there, and anything fetched is saved to it. Returns what was
+// asked of the indexer.
 async fn fetch_info_files_into_cache(
+    client: &impl IndexerClient,
     cache_signal: RwSignal<InfoFilesCache>,
-    info_hashes: Vec<String>,
-) -> Result<()> {
-    let result = get_info_files(&info_hashes).await;
-    cache_signal.update(|cache| match result {
+    store: Option<&InfoFilesStore>,
+    mut info_hashes: Vec<InfoHash>,
+) -> Result<Vec<InfoHash>> {
+    if let Some(store) = store {
+        let saved = store.get_many(&info_hashes).await;
+        info_hashes.retain(|info_hash| {
+            !saved
+                .iter()
+                .any(|info_files| info_files.info.info_hash == *info_hash)
+        });
+        cache_signal.update(|cache| {
+            for info_files in saved {
+                cache.insert(info_files.info.info_hash, Some(Ok(info_files)));
+            }
+        });
+    }
+    let batching = client.info_files_batching();
+    futures::stream::iter(batching.chunks(&info_hashes))
+        .for_each_concurrent(batching.concurrency.max(1), |chunk| async move {
+            let result = client.get_info_files(chunk).await;
+            let to_save = store.and(result.as_ref().ok().cloned());
+            cache_signal.update(|cache| insert_info_files_result(cache, chunk.to_vec(), result));
+            if let (Some(store), Some(payload)) = (store, to_save) {
+                // From the indexer, so none of them have trackers of their own.
+                store.put_many(&payload, &ImportedTrackers::new()).await;
+            }
+        })
+        .await;
+    Ok(info_hashes)
+}
+
+fn insert_info_files_result(
+    cache: &mut InfoFilesCache,
+    info_hashes: Vec<InfoHash>,
+    result: Result<InfoFilesPayload>,
+) {
+    match result {
         Ok(payload) => {
             for info_hash in info_hashes {
                 cache.insert(
@@ -51,20 +204,54 @@ async fn fetch_info_files_into_cache(
                 );
             }
             for info_files in payload {
-                cache.insert(info_files.info.info_hash.clone(), Some(Ok(info_files)));
+                cache.insert(info_files.info.info_hash, Some(Ok(info_files)));
             }
         }
+        // Whoever cancelled it didn't want the result.
+        Err(err) if matches!(**err, crate::Error::Cancelled) => {}
         Err(err) => {
             for info_hash in info_hashes {
                 cache.insert(info_hash, Some(Err(err.clone())));
             }
         }
+    }
+}
+
+// An imported .torrent only has what's in the file, but the indexer might know how its swarm is
+// doing. It's also how cached swarm info is refreshed. Returns the updated entry.
+async fn merge_indexed_swarm_info(
+    client: &impl IndexerClient,
+    cache_signal: RwSignal<InfoFilesCache>,
+    info_hash: InfoHash,
+) -> Option<InfoFiles> {
+    let indexed = match client.get_info_files(&[info_hash]).await {
+        Ok(payload) => payload
+            .into_iter()
+            .find(|info_files| info_files.info.info_hash == info_hash),
+        Err(err) => {
+            warn!("looking up swarm info for {}: {}", info_hash, err);
+            return None;
+        }
+    };
+    let Some(indexed) = indexed else {
+        info!("torrent {} isn't indexed", info_hash);
+        return None;
+    };
+    let mut updated = None;
+    cache_signal.update(|cache| {
+        if let Some(Some(Ok(info_files))) = cache.get_mut(&info_hash) {
+            info_files.info.info_id = indexed.info.info_id;
+            info_files.info.scrape_data = indexed.info.scrape_data;
+            info_files.info.scrape_time = indexed.info.scrape_time;
+            updated = Some(info_files.clone());
+        }
     });
-    Ok(())
+    updated
 }
 
 pub fn file_types(info_files: &InfoFiles) -> Vec<String> {
-    if let [File { path: None, .. }] = info_files.files[..] {
+    let files = info_files.files();
+    if let [File { path: None, .. }] = files[..] {
         return Path::new(&info_files.info.name.as_str())
             .extension()
             .and_then(OsStr::to_str)
@@ -72,13 +259,13 @@ pub fn file_types(info_files: &InfoFiles) -> Vec<String> {
             .into_iter()
             .collect();
     }
-    let mut ext_refs = HashMap::<&OsStr, FileLength>::with_capacity(info_files.files.len());
-    for file in &info_files.files {
+    let mut ext_refs = HashMap::<&OsStr, FileLength>::with_capacity(files.len());
+    for file in files.iter() {
         let ext = match file
             .path
             .as_ref()
             .and_then(|x| x.last())
-            .map(Path::new)
+            .map(|x| Path::new(x.as_str()))
             .and_then(Path::extension)
         {
             Some(some) => some,
@@ -113,117 +300,107 @@ pub fn file_types(info_files: &InfoFiles) -> Vec<String> {
         .collect()
 }
 
+// What the result memos want from each torrent's info files, worked out once rather than on every
+// run. A torrent's files never change, so these are kept by info hash.
+#[derive(PartialEq, Eq, Debug)]
+struct FileSummary {
+    num_files: usize,
+    file_types: Vec<String>,
+    // For sorting by file types.
+    file_types_key: String,
+    // For the facets, which have to see every file.
+    extensions: HashSet<String>,
+}
+
+impl FileSummary {
+    fn new(info_files: &InfoFiles) -> Self {
+        let file_types = file_types(info_files);
+        Self {
+            num_files: info_files.files().len(),
+            file_types_key: file_types.join(" "),
+            file_types,
+            extensions: crate::query::file_extensions(info_files),
+        }
+    }
+}
+
+type FileSummaries = HashMap<InfoHash, Rc<FileSummary>>;
+
+// Only info files that weren't in previous are summarized.
+fn update_file_summaries(
+    previous: Option<&FileSummaries>,
+    cache: &InfoFilesCache,
+) -> FileSummaries {
+    cache
+        .iter()
+        .filter_map(|(info_hash, cached)| {
+            let info_files = cached.as_ref()?.as_ref().ok()?;
+            let summary = previous
+                .and_then(|previous| previous.get(info_hash).cloned())
+                .unwrap_or_else(|| Rc::new(FileSummary::new(info_files)));
+            Some((*info_hash, summary))
+        })
+        .collect()
+}
+
 pub fn mount_to_body() {
     ::leptos::mount_to_body(|cx| view! { cx, <App/> })
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub(crate) struct FileView {
-    pub depth: usize,
-    pub name: String,
-    pub children: Vec<FileView>,
-    pub size: u64,
-    pub so: Option<usize>,
-    pub expanded: bool,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SelectionState {
+    All,
+    Partial,
+    None,
 }
 
-impl FileView {
-    pub fn from_file_rows<'a>(file_rows: impl IntoIterator<Item = &'a FileRow> + Copy) -> Self {
-        let this_file_row = &FileRow {
-            path: vec![],
-            dir: true,
-            size: None,
-            so: None,
-        };
-        Self::from_file_rows_inner(this_file_row, file_rows)
-    }
-
-    fn from_file_rows_inner<'a>(
-        target: &FileRow,
-        file_rows: impl IntoIterator<Item = &'a FileRow> + Copy,
-    ) -> Self {
-        let children = if target.dir {
-            let mut children: Vec<FileView> = file_rows
-                .into_iter()
-                .filter(|file_row: &&FileRow| {
-                    let target_len = target.path.len();
-                    file_row.path.len() == target_len + 1
-                        && target.path.iter().eq(file_row.path.iter().take(target_len))
-                })
-                .map(|file_row| Self::from_file_rows_inner(file_row, file_rows))
-                .collect();
-            let collator = new_collator();
-            children.sort_by(|left, right| {
-                collator
-                    .compare(&left.name, &right.name)
-                    .then(
-                        left.children
-                            .is_empty()
-                            .cmp(&right.children.is_empty())
-                            .reverse(),
-                    )
-                    .then(left.size.cmp(&right.size).reverse())
-            });
-            children
+// File indexes (FileRow::so) ticked in the file tree, provided as context to the rows.
+#[derive(Clone, Debug, PartialEq, Default)]
+struct FileSelection(HashSet<usize>);
+
+impl FileSelection {
+    fn all(file_rows: &[FileRow]) -> Self {
+        Self(file_rows.iter().filter_map(|row| row.so).collect())
+    }
+
+    fn state(&self, indexes: &[usize]) -> SelectionState {
+        let selected = indexes
+            .iter()
+            .filter(|index| self.0.contains(index))
+            .count();
+        if selected == 0 {
+            SelectionState::None
+        } else if selected == indexes.len() {
+            SelectionState::All
         } else {
-            vec![]
-        };
-        FileView {
-            name: target.leaf().cloned().unwrap_or_default(),
-            so: target.so,
-            size: target.size.unwrap_or_default() as u64
-                + children.iter().map(|file_view| file_view.size).sum::<u64>(),
-            children,
-            expanded: false,
-            depth: target.path.len(),
+            SelectionState::Partial
         }
     }
-}
 
-impl IntoView for FileView {
-    fn into_view(self, cx: Scope) -> View {
-        let (expanded, set_expanded) = create_signal(cx, self.expanded);
-        let name = self.name.clone();
-        let name = if self.children.is_empty() {
-            name.into_view(cx)
-        } else {
-            view! { cx,
-                <a href="#" on:click=move |_cx| set_expanded(!expanded())>
-                    {name}
-                </a>
-            }
-            .into_view(cx)
-        };
-        let child_rows = {
-            let children = self.children.clone();
-            move || {
-                if expanded() {
-                    Some(children.clone().collect_view(cx))
-                } else {
-                    None
-                }
+    // Like clicking a tri-state checkbox: anything less than fully selected becomes fully
+    // selected.
+    fn toggle(&mut self, indexes: &[usize]) {
+        if self.state(indexes) == SelectionState::All {
+            for index in indexes {
+                self.0.remove(index);
             }
-        };
-        let row = self;
-        let dir = !row.children.is_empty();
-        view! { cx,
-            <tr>
-                <td style:padding-left=format!("{}em", row.depth)>
-                    <input type="checkbox" disabled/>
-                    <i
-                        style:width="1em"
-                        style:padding-right="0.5em"
-                        class="fa-regular"
-                        class:fa-file=move || !dir
-                        class:fa-folder=move || dir
-                    ></i>
-                    {name}
-                </td>
-                <td>{format_size(row.size)}</td>
-            </tr>
-            {child_rows}
+        } else {
+            self.0.extend(indexes);
         }
-        .into()
+    }
+
+    // The number and total size of the selected files.
+    fn totals(&self, file_rows: &[FileRow]) -> (usize, i64) {
+        file_rows
+            .iter()
+            .filter(|row| row.so.map_or(false, |so| self.0.contains(&so)))
+            .fold((0, 0), |(count, size), row| {
+                (count + 1, size + row.size.unwrap_or_default())
+            })
+    }
+
+    fn indexes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().copied()
     }
 }
 
@@ -232,3 +409,193 @@ fn format_size(size: u64) -> String {
     options.decimal_places = 1;
     humansize::format_size(size, options)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_indexer::FakeIndexer;
+    use filerow::FileRowTree;
+    use futures::executor::block_on;
+    use virtual_tree::FlatFileTree;
+
+    const AARON_SWARTZ: &str = "40f3761b9080949ca6ffed3522ad872bc0bef41b";
+    const VAULT: &str = "3670d38c31d660d690384731483e145695586797";
+
+    fn info_hash(s: &str) -> InfoHash {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_imported_torrent_magnet() {
+        let metainfo = crate::metainfo::Metainfo::from_bytes(
+            b"d8:announce25:http://t.example/announce\
+            4:infod6:lengthi5e4:name5:a.iso12:piece lengthi16384e\
+            6:pieces20:xxxxxxxxxxxxxxxxxxxxee",
+        )
+        .unwrap();
+        let user_trackers = TrackerList(vec![
+            "udp://user.example:1337/announce".to_owned(),
+            "http://t.example/announce".to_owned(),
+        ]);
+        let magnet = torrent_magnet(
+            &metainfo.info_files.info,
+            &metainfo.trackers,
+            &user_trackers,
+        )
+        .to_string();
+        assert!(magnet.ends_with(
+            "&tr=http%3A%2F%2Ft.example%2Fannounce&tr=udp%3A%2F%2Fuser.example%3A1337%2Fannounce"
+        ));
+    }
+
+    #[test]
+    fn test_with_query_param() {
+        let search = "?s=big+buck&sort=name";
+        assert_eq!(
+            with_query_param(search, "sort", Some("-size")),
+            "?s=big+buck&sort=-size"
+        );
+        assert_eq!(with_query_param(search, "sort", None), "?s=big+buck");
+        assert_eq!(with_query_param("sort=name", "sort", None), "");
+        assert_eq!(with_query_param("", "s", Some("a&b")), "?s=a%26b");
+    }
+
+    #[test]
+    fn test_torrent_path() {
+        let info_hash = InfoHash::V1([1; 20]);
+        assert_eq!(
+            torrent_path_info_hash(&torrent_path(&info_hash)),
+            Some(info_hash)
+        );
+        assert_eq!(torrent_path_info_hash("/torrent/nope"), None);
+        assert_eq!(torrent_path_info_hash("/"), None);
+    }
+
+    fn fetch_into_cache(fake: FakeIndexer, info_hashes: &[&str]) -> InfoFilesCache {
+        let indexer = Indexer::with_transport(IndexerEndpoint::new("/dhtindex/"), fake)
+            .with_retry(RetryPolicy::none());
+        let info_hashes: Vec<InfoHash> = info_hashes.iter().map(|&ih| info_hash(ih)).collect();
+        let result = block_on(indexer.get_info_files(&info_hashes));
+        let mut cache = InfoFilesCache::new();
+        insert_info_files_result(&mut cache, info_hashes, result);
+        cache
+    }
+
+    fn cached_error(cache: &InfoFilesCache, ih: &str) -> Option<String> {
+        match cache.get(&info_hash(ih)) {
+            Some(Some(Err(err))) => Some(err.to_string()),
+            _ => None,
+        }
+    }
+
+    fn search_page(total: usize, info_hashes: &[u8]) -> InfosSearch {
+        InfosSearch {
+            total,
+            err: None,
+            items: info_hashes
+                .iter()
+                .map(|&byte| InfoItem {
+                    info_hash: InfoHash::V1([byte; 20]),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_search_results_dedup_pages() {
+        let pages = [search_page(4, &[1, 2]), search_page(4, &[2, 3])];
+        let results = SearchResults::from_pages(&pages);
+        let info_hashes: Vec<_> = results.items.iter().map(|item| item.info_hash).collect();
+        assert_eq!(
+            info_hashes,
+            [
+                InfoHash::V1([1; 20]),
+                InfoHash::V1([2; 20]),
+                InfoHash::V1([3; 20])
+            ]
+        );
+        assert_eq!(results.next_page().offset, 4);
+        assert!(!results.has_more());
+        let results = SearchResults::from_pages(&pages[..1]);
+        assert!(results.has_more());
+        let pages = [search_page(4, &[1, 2]), search_page(4, &[])];
+        assert!(!SearchResults::from_pages(&pages).has_more());
+    }
+
+    #[test]
+    fn test_file_selection() {
+        let file_row = |path: &[&str], so| FileRow {
+            path: path.iter().map(|&part| part.into()).collect(),
+            dir: false,
+            size: Some(10),
+            so: Some(so),
+        };
+        let file_rows = crate::filerow::info_files_to_file_rows(
+            &[
+                file_row(&["s01", "e01.mkv"], 0),
+                file_row(&["s01", "e02.mkv"], 1),
+                file_row(&["notes.txt"], 2),
+            ]
+            .map(|row| UpvertedFile {
+                path: row.path,
+                length: row.size.unwrap(),
+            }),
+        );
+        let tree = FlatFileTree::new(&FileRowTree::new(&file_rows), "show".to_owned());
+        let root = tree.file_indexes(0);
+        // After the root and notes.txt.
+        let season = tree.file_indexes(2);
+        assert_eq!(season, [0, 1]);
+        let mut selection = FileSelection::all(&file_rows);
+        assert_eq!(selection.state(&root), SelectionState::All);
+        selection.toggle(&season);
+        assert_eq!(selection.state(&season), SelectionState::None);
+        assert_eq!(selection.state(&root), SelectionState::Partial);
+        selection.toggle(&[0]);
+        assert_eq!(selection.state(&season), SelectionState::Partial);
+        assert_eq!(selection.totals(&file_rows), (2, 20));
+        // A partially selected directory becomes fully selected.
+        selection.toggle(&season);
+        assert_eq!(selection.state(&root), SelectionState::All);
+    }
+
+    #[test]
+    fn test_missing_info_hash_cached_as_error() {
+        let mut fake = FakeIndexer::with_repo_fixtures().unwrap();
+        fake.faults.missing.insert(info_hash(VAULT));
+        let cache = fetch_into_cache(fake, &[AARON_SWARTZ, VAULT]);
+        assert!(matches!(
+            cache.get(&info_hash(AARON_SWARTZ)),
+            Some(Some(Ok(_)))
+        ));
+        assert_eq!(
+            cached_error(&cache, VAULT).as_deref(),
+            Some("not included in response")
+        );
+    }
+
+    #[test]
+    fn test_cache_key_ignores_case() {
+        let fake = FakeIndexer::with_repo_fixtures().unwrap();
+        let cache = fetch_into_cache(fake, &[&AARON_SWARTZ.to_uppercase()]);
+        assert_eq!(cache.len(), 1);
+        assert!(matches!(
+            cache.get(&info_hash(AARON_SWARTZ)),
+            Some(Some(Ok(_)))
+        ));
+    }
+
+    #[test]
+    fn test_http_error_cached_for_every_info_hash() {
+        let mut fake = FakeIndexer::with_repo_fixtures().unwrap();
+        fake.faults.http_error = Some((500, "database is locked".to_owned()));
+        let cache = fetch_into_cache(fake, &[AARON_SWARTZ, VAULT]);
+        for info_hash in [AARON_SWARTZ, VAULT] {
+            assert_eq!(
+                cached_error(&cache, info_hash).as_deref(),
+                Some("indexer responded with HTTP 500: database is locked")
+            );
+        }
+    }
+}
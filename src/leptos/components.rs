@@ -1,19 +1,68 @@
 use super::*;
-use crate::filerow::info_files_to_file_rows;
+use crate::filerow::{info_files_to_file_rows, FileRowTree};
+use crate::metainfo::Metainfo;
+use crate::query::{FileFacts, SearchQuery};
 use ::leptos::html::Input;
+use chrono::Utc;
+use export::ExportResults;
+use facets::{count_file_types, FacetState, FileTypeFacets, FileTypeFacetsSidebar};
 use humansize::{format_size, DECIMAL};
+use import::ImportTorrent;
+use info_files_store::{CacheUsage, InfoFilesStore, InfoFilesStoreResource};
+use refresh::InfoFilesRefresh;
+use settings::IndexerSettings;
+use sort::{sort_items, SortColumn, SortOrder, SORT_QUERY_PARAM};
+use virtual_tree::{FlatFileTree, VirtualFileTree};
 use web_sys::SubmitEvent;
 
+fn view_error(cx: Scope, err: &crate::Error) -> View {
+    let hint = err
+        .hint()
+        .map(|hint| view! { cx, <p class="error-hint">{hint}</p> });
+    view! { cx,
+        <span class="error">{err.to_string()}</span>
+        {hint}
+    }
+    .into_view(cx)
+}
+
 fn list_errors(cx: Scope, errors: RwSignal<Errors>) -> impl IntoView {
     errors
         .get()
         .into_iter()
-        .map(|(_, e)| view! { cx, <li>{e.to_string()}</li> })
+        .map(|(_, e)| {
+            let message = match e.downcast_ref::<CloneableError>() {
+                Some(err) => view_error(cx, err),
+                None => e.to_string().into_view(cx),
+            };
+            view! { cx, <li>{message}</li> }
+        })
         .collect_view(cx)
 }
 
 #[component]
 pub fn App(cx: Scope) -> impl IntoView {
+    let indexer: RwSignal<Indexer> = create_rw_signal(
+        cx,
+        Indexer::new(IndexerEndpoint::resolve()).with_compression(Compression::load()),
+    );
+    provide_context(cx, indexer);
+    let trackers = create_rw_signal(cx, TrackerList::load());
+    provide_context(cx, trackers);
+    let cache_usage = create_rw_signal(cx, CacheUsage::default());
+    provide_context(cx, cache_usage);
+    // Searches wait for this, so a reload finds what was saved before fetching anything.
+    let info_files_store: InfoFilesStoreResource = create_local_resource(
+        cx,
+        || (),
+        move |_| async move {
+            InfoFilesStore::open(cache_usage)
+                .await
+                .map_err(|err| warn!("opening info files cache: {:?}", err))
+                .ok()
+        },
+    );
+    provide_context(cx, info_files_store);
     view! { cx,
         <div class="body">
             <div class="content">
@@ -27,7 +76,7 @@ pub fn App(cx: Scope) -> impl IntoView {
 
 fn with_cached_info_files<T>(
     cache: ReadSignal<InfoFilesCache>,
-    info_hash: ReadSignal<Option<String>>,
+    info_hash: Memo<Option<InfoHash>>,
     with: impl Fn(&InfoFiles) -> T,
 ) -> Option<T> {
     cache.with(|cache| {
@@ -44,60 +93,233 @@ fn with_cached_info_files<T>(
 
 #[component]
 fn InsideRouter(cx: Scope) -> impl IntoView {
-    // let search_query = move || use_query_map(cx)().get("s").cloned().unwrap_or_default();
-    let (search_query, set_search_query) = create_signal(cx, "".to_owned());
-    let torrent_ih = create_rw_signal(cx, None);
-    provide_context(cx, torrent_ih.write_only());
-    let search_resource: SearchResultResource =
-        create_local_resource(cx, search_query, |query| async move {
-            if query.is_empty() {
-                return Ok(None);
+    // Everything a link or bookmark should bring back is in the URL: the search (s=), the sort
+    // order, and which torrent is open.
+    let location = use_location(cx);
+    let search_query: Signal<String> = create_memo(cx, move |_| {
+        location
+            .query
+            .with(|query| query.get("s").cloned().unwrap_or_default())
+    })
+    .into();
+    let torrent_ih: Memo<Option<InfoHash>> = create_memo(cx, move |_| {
+        location.pathname.with(|path| torrent_path_info_hash(path))
+    });
+    let indexer = use_context::<RwSignal<Indexer>>(cx).expect("indexer to be provided");
+    let trackers = use_context::<RwSignal<TrackerList>>(cx).expect("trackers to be provided");
+    let info_files_store =
+        use_context::<InfoFilesStoreResource>(cx).expect("info files store to be provided");
+    // The indexer gets the text part, the filters are applied to whatever it returns. Changing
+    // only the filters doesn't search again.
+    let parsed_query = create_memo(cx, move |_| {
+        search_query.with(|query| query.parse::<SearchQuery>().map_err(|err| err.to_string()))
+    });
+    let server_query = create_memo(cx, move |_| {
+        parsed_query.with(|parsed| {
+            parsed
+                .as_ref()
+                .map(SearchQuery::server_query)
+                .unwrap_or_default()
+        })
+    });
+    let query_filter: Signal<SearchQuery> =
+        (move || parsed_query.with(|parsed| parsed.clone().unwrap_or_default())).derive_signal(cx);
+    let query_error: Signal<Option<String>> =
+        (move || parsed_query.with(|parsed| parsed.as_ref().err().cloned())).derive_signal(cx);
+    let sort: Memo<Option<SortOrder>> = create_memo(cx, move |_| {
+        location.query.with(|query| {
+            query
+                .get(SORT_QUERY_PARAM)
+                .and_then(|value| value.parse().ok())
+        })
+    });
+    let facets = create_rw_signal(cx, FileTypeFacets::default());
+    // Aborts the previous search when a new one starts.
+    let search_cancel = store_value(cx, CancelToken::new());
+    let search_resource: SearchResultResource = create_local_resource(
+        cx,
+        move || (server_query(), indexer()),
+        move |(query, indexer)| {
+            let cancel = CancelToken::new();
+            search_cancel.update_value(|previous| {
+                std::mem::replace(previous, cancel.clone()).cancel();
+            });
+            async move {
+                if query.is_empty() {
+                    return Ok(None);
+                }
+                let indexer = indexer.cancellable(cancel);
+                Ok(Some(indexer.search(query, SearchPage::first()).await?))
+            }
+        },
+    );
+    // Pages after the first, appended by load more.
+    let more_pages = create_rw_signal(cx, Vec::<InfosSearch>::new());
+    let loading_more = create_rw_signal(cx, false);
+    let load_more_error = create_rw_signal(cx, None::<CloneableError>);
+    let load_more_cancel = store_value(cx, CancelToken::new());
+    create_effect(cx, move |_| {
+        server_query.with(|_| ());
+        indexer.with(|_| ());
+        load_more_cancel.with_value(CancelToken::cancel);
+        more_pages.set(vec![]);
+        load_more_error.set(None);
+        // The types on offer are about to change.
+        facets.set(Default::default());
+    });
+    let search_results = create_memo(cx, move |_| {
+        let first = search_resource.read(cx).and_then(Result::ok).flatten()?;
+        Some(
+            more_pages
+                .with(|more| SearchResults::from_pages(std::iter::once(&first).chain(more.iter()))),
+        )
+    });
+    let load_more = move || {
+        if loading_more.get_untracked() {
+            return;
+        }
+        let Some(page) = search_results.with_untracked(|results| {
+            results
+                .as_ref()
+                .filter(|results| results.has_more())
+                .map(SearchResults::next_page)
+        }) else {
+            return;
+        };
+        let query = server_query.get_untracked();
+        let cancel = CancelToken::new();
+        load_more_cancel.set_value(cancel.clone());
+        let indexer = indexer.get_untracked().cancellable(cancel.clone());
+        loading_more.set(true);
+        load_more_error.set(None);
+        spawn_local(async move {
+            let result = indexer.search(query.clone(), page).await;
+            // Another load more has taken over.
+            if load_more_cancel.with_value(|current| *current != cancel) {
+                return;
+            }
+            loading_more.set(false);
+            // Drop the page if the search or indexer changed while it was in flight, the reset
+            // effect cancels it for either.
+            if cancel.is_cancelled() || server_query.get_untracked() != query {
+                return;
+            }
+            match result {
+                Ok(search) => more_pages.update(|pages| pages.push(search)),
+                Err(err) if matches!(**err, crate::Error::Cancelled) => {}
+                Err(err) => load_more_error.set(Some(err)),
             }
-            Ok(Some(search(query).await?))
         });
+    };
     let info_files_cache = create_rw_signal(cx, InfoFilesCache::new());
-    create_effect(cx, move |attempted: Option<HashSet<_>>| {
-        let mut attempted = attempted.unwrap_or_default();
+    let file_summaries = create_memo(cx, move |previous| {
+        info_files_cache.with(|cache| update_file_summaries(previous, cache))
+    });
+    let imported_trackers = create_rw_signal(cx, ImportedTrackers::new());
+    provide_context(cx, imported_trackers);
+    // Imported torrents that were saved bring their trackers back with them.
+    create_effect(cx, move |_| {
+        let Some(Some(store)) = info_files_store.read(cx) else {
+            return;
+        };
+        let saved = store.imported_trackers();
+        imported_trackers.update(|imported| {
+            for (info_hash, trackers) in saved {
+                imported.entry(info_hash).or_insert(trackers);
+            }
+        });
+    });
+    let in_flight = store_value(cx, Vec::<InFlightFetch>::new());
+    let refresh = InfoFilesRefresh::new(cx, info_files_cache);
+    provide_context(cx, refresh);
+    create_effect(cx, move |_| {
+        // Still opening, this runs again once it has.
+        let Some(store) = info_files_store.read(cx) else {
+            return;
+        };
         info!("missing info files effect running");
-        let needed = get_needed_info_hashes(cx, torrent_ih(), search_resource);
+        let needed = get_needed_info_hashes(torrent_ih(), search_results);
+        // Abort fetches that nothing on screen wants anymore, like after leaving a torrent or
+        // starting a new search. They're fetched again if they're needed later.
+        in_flight.update_value(|in_flight| {
+            in_flight.retain(|fetch| {
+                if fetch.info_hashes.iter().any(|ih| needed.contains(ih)) {
+                    return true;
+                }
+                fetch.cancel.cancel();
+                refresh.attempted.update_value(|attempted| {
+                    for info_hash in &fetch.info_hashes {
+                        attempted.remove(info_hash);
+                    }
+                });
+                false
+            })
+        });
         let spawn_fetch = move |info_hashes: Vec<_>| {
             if info_hashes.is_empty() {
                 return;
             }
+            let cancel = CancelToken::new();
+            in_flight.update_value(|in_flight| {
+                in_flight.push(InFlightFetch {
+                    info_hashes: info_hashes.clone(),
+                    cancel: cancel.clone(),
+                })
+            });
+            let indexer = indexer.get_untracked().cancellable(cancel.clone());
+            let store = store.clone();
             spawn_local(async move {
-                fetch_info_files_into_cache(info_files_cache, info_hashes)
-                    .await
-                    .expect("fetch info files into cache failed")
+                let requested = fetch_info_files_into_cache(
+                    &indexer,
+                    info_files_cache,
+                    store.as_ref(),
+                    info_hashes,
+                )
+                .await
+                .expect("fetch info files into cache failed");
+                refresh.fetched(&requested);
+                in_flight
+                    .update_value(|in_flight| in_flight.retain(|fetch| fetch.cancel != cancel));
             })
         };
         info_files_cache.with(|cache| {
-            let mut missing = get_missing_info_hashes(cache, needed);
-            missing.retain(|info_hash| !attempted.contains(info_hash));
+            let mut missing = get_missing_info_hashes(cache, &needed);
+            refresh.attempted.update_value(|attempted| {
+                missing.retain(|info_hash| !attempted.contains(info_hash));
+                attempted.extend(missing.iter().copied());
+            });
             const FETCH_INDIVIDUALLY: bool = false;
             if FETCH_INDIVIDUALLY {
                 for info_hash in missing {
-                    assert!(attempted.insert(info_hash.clone()));
                     spawn_fetch(vec![info_hash]);
                 }
             } else {
-                attempted.extend(missing.clone());
                 spawn_fetch(missing);
             }
         });
-        attempted
+    });
+    // Whatever's cached for the open torrent shows straight away, and is refreshed behind it if
+    // it's old. Runs again as the entry arrives.
+    create_effect(cx, move |_| {
+        let Some(info_hash) = torrent_ih() else {
+            return;
+        };
+        let Some(store) = info_files_store.read(cx) else {
+            return;
+        };
+        info_files_cache.track();
+        let own_trackers =
+            imported_trackers.with_untracked(|imported| imported.get(&info_hash).cloned());
+        refresh.revalidate(indexer.get_untracked(), store, info_hash, own_trackers);
     });
     let file_rows: Signal<Option<Vec<FileRow>>> = create_memo(cx, move |_last| {
-        with_cached_info_files(
-            info_files_cache.read_only(),
-            torrent_ih.read_only(),
-            |info_files| {
-                debug!("running file rows memo for {}", &info_files.info.info_hash);
-                info_files_to_file_rows(&info_files.upverted_files())
-            },
-        )
+        with_cached_info_files(info_files_cache.read_only(), torrent_ih, |info_files| {
+            debug!("running file rows memo for {}", &info_files.info.info_hash);
+            info_files_to_file_rows(&info_files.upverted_files())
+        })
     })
     .into();
-    let search_view = move || {
+    let search_view = move |cx: Scope| {
         {
             view! { cx,
                 <Suspense fallback=move || {
@@ -105,49 +327,139 @@ fn InsideRouter(cx: Scope) -> impl IntoView {
                 }>
                     <SearchResult
                         herp=search_resource
+                        search_results
                         info_files_cache=info_files_cache.read_only()
-                        set_torrent_ih=torrent_ih.write_only()
-                        search_query=search_query.into()
+                        file_summaries
+                        search_query
+                        query_filter
+                        sort
+                        facets
+                        loading_more=loading_more.read_only()
+                        load_more_error=load_more_error.read_only()
+                        load_more
                     />
                 </Suspense>
             }
         }
         .into_view(cx)
     };
+    let file_types: Signal<Option<Vec<String>>> = create_memo(cx, move |_| {
+        with_cached_info_files(info_files_cache.read_only(), torrent_ih, file_types)
+    })
+    .into();
     let with_current_info = move || {
-        with_cached_info_files(
-            info_files_cache.read_only(),
-            torrent_ih.read_only(),
-            |info_files| info_files.info.clone(),
-        )
+        with_cached_info_files(info_files_cache.read_only(), torrent_ih, |info_files| {
+            info_files.info.clone()
+        })
     };
-    let contents_view = move || match torrent_ih() {
-        Some(info_hash) => {
-            let info = with_current_info.derive_signal(cx);
-            view! { cx, <TorrentInfo file_rows info info_hash/> }
+    let torrent_view = move |cx: Scope| {
+        move || match torrent_ih() {
+            Some(info_hash) => {
+                let info = with_current_info.derive_signal(cx);
+                view! { cx, <TorrentInfo file_rows file_types info info_hash/> }.into_view(cx)
+            }
+            None => {
+                view! { cx, <p class="error">"That isn't a valid info hash."</p> }.into_view(cx)
+            }
         }
-        .into_view(cx),
-        None => search_view.into_view(cx),
     };
-    let set_search_query = move |query| {
-        cx.batch(|| {
-            torrent_ih.set(None);
-            set_search_query(query);
-        })
+    restore_results_scroll(cx, location.pathname, location.search);
+    // A new search is a new history entry, so back goes to the previous one.
+    let set_search_query = move |query: String| {
+        let search = location.search.get_untracked();
+        navigate_to(
+            cx,
+            &format!("/{}", with_query_param(&search, "s", Some(&query))),
+            false,
+        );
+    };
+    // Imports go in the cache like anything fetched, so they show up the same way.
+    let on_import = move |metainfo: Metainfo, lookup: bool| {
+        let info_hash = metainfo.info_files.info.info_hash;
+        // Before it's in the cache, so revalidation knows whether to look it up.
+        refresh.imported(info_hash, lookup);
+        imported_trackers.update(|imported| {
+            imported.insert(info_hash, metainfo.trackers);
+        });
+        info_files_cache.update(|cache| {
+            cache.insert(info_hash, Some(Ok(metainfo.info_files)));
+        });
+        let search = location.search.get_untracked();
+        navigate_to(
+            cx,
+            &format!("{}{}", torrent_path(&info_hash), query_string(&search)),
+            false,
+        );
     };
     view! { cx,
         <h1>{"DHT search"}</h1>
         <div class="search-form">
-            <SearchForm search_query set_search_query/>
+            <SearchForm search_query set_search_query query_error/>
         </div>
+        <ImportTorrent on_import/>
+        <IndexerSettings indexer trackers/>
         <ErrorBoundary fallback=|cx, errors| {
             view! { cx, <ul>{list_errors(cx, errors)}</ul> }
-        }>{contents_view}</ErrorBoundary>
+        }>
+            <Routes>
+                <Route path="/" view=search_view/>
+                <Route path="/torrent/:info_hash" view=torrent_view/>
+            </Routes>
+        </ErrorBoundary>
     }
 }
 
+// Browsers only restore the scroll position on a full page load, so the results page remembers
+// where it was for each query string and goes back there when it's returned to.
+fn restore_results_scroll(cx: Scope, pathname: Memo<String>, search: Memo<String>) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    if let Ok(history) = window.history() {
+        if let Err(err) = history.set_scroll_restoration(web_sys::ScrollRestoration::Manual) {
+            warn!("setting scroll restoration: {:?}", err);
+        }
+    }
+    let positions = store_value(cx, HashMap::<String, f64>::new());
+    let on_results = move || pathname.with_untracked(|path| path == "/");
+    let handle = window_event_listener(ev::scroll, move |_| {
+        if !on_results() {
+            return;
+        }
+        let Some(scroll_y) = web_sys::window().and_then(|window| window.scroll_y().ok()) else {
+            return;
+        };
+        positions.update_value(|positions| {
+            positions.insert(search.get_untracked(), scroll_y);
+        });
+    });
+    on_cleanup(cx, move || handle.remove());
+    create_effect(cx, move |_| {
+        pathname.track();
+        let search = search.get();
+        if !on_results() {
+            return;
+        }
+        let Some(scroll_y) = positions.with_value(|positions| positions.get(&search).copied())
+        else {
+            return;
+        };
+        // Wait for the results to be rendered again.
+        request_animation_frame(move || {
+            if let Some(window) = web_sys::window() {
+                window.scroll_to_with_x_and_y(0.0, scroll_y);
+            }
+        });
+    });
+}
+
 #[component]
-fn SearchForm<F>(cx: Scope, search_query: ReadSignal<String>, set_search_query: F) -> impl IntoView
+fn SearchForm<F>(
+    cx: Scope,
+    search_query: Signal<String>,
+    set_search_query: F,
+    query_error: Signal<Option<String>>,
+) -> impl IntoView
 where
     F: Fn(String) + 'static,
 {
@@ -168,23 +480,35 @@ where
                 style="width: 100%"
                 type="search"
                 name="s"
+                placeholder="words \"a phrase\" -excluded size:>1GB seeders:>=10 age:<30d ext:mkv files:<5"
                 prop:value=search_query
                 node_ref=input_element
             />
         </form>
+        {move || query_error().map(|err| view! { cx, <p class="error">{err}</p> })}
+    }
+}
+
+// Relative, with the absolute time on hover.
+fn view_timestamp(cx: Scope, timestamp: Timestamp) -> impl IntoView {
+    let absolute = timestamp.to_string();
+    view! { cx,
+        <time datetime=absolute.clone() title=absolute>
+            {timestamp.relative()}
+        </time>
     }
 }
 
 #[component]
 fn TorrentInfoMetadataItem<K, V>(cx: Scope, key: K, value: V) -> impl IntoView
 where
-    V: Display,
+    V: IntoView,
     K: IntoView,
 {
     view! { cx,
         <tr>
             <td>{key}</td>
-            <td>{value.to_string()}</td>
+            <td>{value}</td>
         </tr>
     }
 }
@@ -194,46 +518,156 @@ fn TorrentInfo(
     cx: Scope,
     info: Signal<Option<Info>>,
     file_rows: Signal<Option<Vec<FileRow>>>,
-    info_hash: String,
+    file_types: Signal<Option<Vec<String>>>,
+    info_hash: InfoHash,
 ) -> impl IntoView {
-    let root_file_view: Signal<Option<FileView>> = create_memo(cx, move |_| {
+    let trackers = use_context::<RwSignal<TrackerList>>(cx).expect("trackers to be provided");
+    let imported_trackers =
+        use_context::<RwSignal<ImportedTrackers>>(cx).expect("imported trackers to be provided");
+    let selection = create_rw_signal(cx, FileSelection::default());
+    provide_context(cx, selection);
+    // Everything starts selected, like adding a torrent to a client.
+    create_effect(cx, move |_| {
+        file_rows.with(|file_rows| {
+            selection.set(FileSelection::all(file_rows.as_deref().unwrap_or_default()))
+        })
+    });
+    let magnet = create_memo(cx, move |_| {
+        info.with(|info| {
+            info.as_ref().map(|info| {
+                let exact_length = file_rows.with(|file_rows| {
+                    file_rows
+                        .as_ref()
+                        .map(|file_rows| file_rows.iter().filter_map(|row| row.size).sum::<i64>())
+                });
+                let magnet = imported_trackers.with(|imported| {
+                    trackers.with(|trackers| {
+                        let own_trackers = imported.get(&info_hash).map_or(&[][..], Vec::as_slice);
+                        torrent_magnet(info, own_trackers, trackers)
+                    })
+                });
+                match exact_length {
+                    Some(exact_length) => magnet.with_exact_length(exact_length as u64),
+                    None => magnet,
+                }
+            })
+        })
+    });
+    // Kept apart from the rest so ticking files doesn't rebuild the file tree.
+    let selection_view = move || {
+        file_rows.with(|file_rows| {
+            let file_rows = file_rows.as_ref()?;
+            let total = file_rows.iter().filter(|row| row.so.is_some()).count();
+            let (count, size) = selection.with(|selection| selection.totals(file_rows));
+            let selected_magnet = if count > 0 && count < total {
+                magnet.with(|magnet| {
+                    magnet.clone().map(|magnet| {
+                        selection
+                            .with(|selection| magnet.with_select_only(selection.indexes()))
+                            .to_string()
+                    })
+                })
+            } else {
+                None
+            };
+            let selected_magnet = selected_magnet.map(|magnet_link| {
+                view! { cx,
+                    <a href=magnet_link>
+                        <i class="fa fa-magnet"></i>
+                        " Magnet for selected files"
+                    </a>
+                }
+            });
+            Some(view! { cx,
+                <p class="selection">
+                    {format!(
+                        "{} of {} files selected, {} ",
+                        count, total, format_size(size as u64, DECIMAL)
+                    )}
+                    {selected_magnet}
+                </p>
+            })
+        })
+    };
+    // Only the name is wanted, not every refresh of the swarm info.
+    let name = create_memo(cx, move |_| {
+        info.with(|info| info.as_ref().map(|info| info.name.to_string()))
+    });
+    let file_tree: Memo<Option<Rc<FlatFileTree>>> = create_memo(cx, move |_| {
         file_rows
             .with(|file_rows| {
                 file_rows.as_ref().map(|file_rows| {
-                    info.with(|info| {
-                        info.as_ref().map(|info| {
-                            let mut root = FileView::from_file_rows(file_rows);
-                            root.expanded = true;
-                            root.name = info.name.to_string();
-                            root
-                        })
-                    })
+                    name()
+                        .map(|name| Rc::new(FlatFileTree::new(&FileRowTree::new(file_rows), name)))
                 })
             })
             .flatten()
-    })
-    .derive_signal(cx);
+    });
+    let expanded = create_rw_signal(cx, HashSet::new());
+    create_effect(cx, move |_| {
+        file_tree.with(|file_tree| {
+            if let Some(file_tree) = file_tree {
+                expanded.set(file_tree.initially_expanded.clone());
+            }
+        })
+    });
+    // Outside the metadata, so refreshed swarm info doesn't scroll the files back to the top.
+    let files_view = move || {
+        file_tree
+            .with(|file_tree| {
+                file_tree
+                    .clone()
+                    .map(|tree| view! { cx, <VirtualFileTree tree expanded/> }.into_view(cx))
+            })
+            .unwrap_or_else(|| view! { cx, <p>Loading...</p> }.into_view(cx))
+    };
     move || {
-        let mut magnet_link_view = None;
         let mut metadata_items = vec![];
-        info.with(|info| info.as_ref().map(|info|{
-            let magnet_link = make_magnet_link(&info.info_hash);
-            magnet_link_view = Some(view! { cx,
+        let magnet_link_view = magnet.with(|magnet| {
+            magnet.as_ref().map(|magnet| {
+                let magnet_link = magnet.to_string();
+                view! { cx,
                     <p>
                         <a href=&magnet_link>
                             <i class="fa fa-magnet"></i>
                             {magnet_link}
                         </a>
                     </p>
-                });
-            metadata_items
-                .push(view! { cx, <TorrentInfoMetadataItem key="Swarm" value=&info.scrape_data/> });
+                }
+            })
+        });
+        info.with(|info| info.as_ref().map(|info|{
+            metadata_items.push(
+                view! { cx, <TorrentInfoMetadataItem key="Swarm" value=info.scrape_data.to_string()/> },
+            );
+            metadata_items.push(
+                view! { cx, <TorrentInfoMetadataItem key="Infohash" value=info.info_hash.to_string()/> },
+            );
+            if let Some(info_hash_v2) = info.info_hash_v2 {
+                metadata_items.push(
+                    view! { cx, <TorrentInfoMetadataItem key="Infohash v2" value=info_hash_v2.to_string()/> },
+                );
+            }
+            metadata_items.push(
+                view! { cx, <TorrentInfoMetadataItem key="Version" value=info.version().label()/> },
+            );
+            if !info.name.is_utf8() {
+                metadata_items.push(
+                    view! { cx, <TorrentInfoMetadataItem key="Name Encoding" value=info.name.encoding_name()/> },
+                );
+            }
+            // Let people work out the name themselves when we probably got it wrong.
+            if info.name.is_doubtful() {
+                metadata_items.push(
+                    view! { cx, <TorrentInfoMetadataItem key="Raw Name" value=view! { cx, <code>{info.name.raw_hex()}</code> }/> },
+                );
+            }
             metadata_items.push(
-                view! { cx, <TorrentInfoMetadataItem key="Infohash" value=&info.info_hash/> },
+                view! { cx, <TorrentInfoMetadataItem key="Age" value=view_timestamp(cx, info.age)/> },
             );
-            metadata_items
-                .push(view! { cx, <TorrentInfoMetadataItem key="Age" value=&info.age/> });
-            metadata_items.push(view! { cx, <TorrentInfoMetadataItem key="Scrape Time" value=&info.scrape_time/> });
+            metadata_items.push(view! { cx,
+                <TorrentInfoMetadataItem key="Scrape Time" value=view_timestamp(cx, info.scrape_time)/>
+            });
         }));
         file_rows.with(|file_rows| {
             file_rows.as_ref().map(|file_rows| {
@@ -242,13 +676,11 @@ fn TorrentInfo(
                 );
             })
         });
-        let files_view = root_file_view
-            .with(|root_file_view| {
-                root_file_view.clone().map(|root_file_view| {
-                    view! { cx, <TorrentFilesNested root_file_view/> }.into_view(cx)
-                })
-            })
-            .unwrap_or_else(|| view! { cx, <p>Loading...</p> }.into_view(cx));
+        if let Some(file_types) = file_types() {
+            metadata_items.push(
+                view! { cx, <TorrentInfoMetadataItem key="File Types" value=view_file_types(cx, file_types)/> },
+            );
+        }
         let metadata_items_view = if metadata_items.is_empty() {
             None
         } else {
@@ -256,9 +688,10 @@ fn TorrentInfo(
         };
         view! { cx,
             <section class="torrent-info">
-            <h3>Torrent Info for {info_hash.clone()}</h3>
+            <h3>Torrent Info for {info_hash.to_string()}</h3>
             {magnet_link_view}
             {metadata_items_view}
+            {selection_view}
             {files_view}
             </section>
         }
@@ -266,32 +699,121 @@ fn TorrentInfo(
 }
 
 #[component]
-fn TorrentFilesNested(cx: Scope, root_file_view: FileView) -> impl IntoView {
-    view! { cx,
-        <table class="torrent-files">
-            <caption>"Files"</caption>
-            {root_file_view}
-        </table>
-    }
-}
-
-#[component]
-fn SearchResult(
+fn SearchResult<F>(
     cx: Scope,
     herp: SearchResultResource,
+    search_results: Memo<Option<SearchResults>>,
     info_files_cache: ReadSignal<InfoFilesCache>,
-    set_torrent_ih: WriteSignal<Option<String>>,
+    file_summaries: Memo<FileSummaries>,
     search_query: Signal<String>,
-) -> impl IntoView {
+    query_filter: Signal<SearchQuery>,
+    sort: Memo<Option<SortOrder>>,
+    facets: RwSignal<FileTypeFacets>,
+    loading_more: ReadSignal<bool>,
+    load_more_error: ReadSignal<Option<CloneableError>>,
+    load_more: F,
+) -> impl IntoView
+where
+    F: Fn() + Copy + 'static,
+{
+    // Rows whose info files haven't arrived stay until the filters can tell.
+    let items: Signal<Vec<InfoItem>> = create_memo(cx, move |_| {
+        let now = Utc::now();
+        search_results.with(|results| {
+            query_filter.with(|query| {
+                file_summaries.with(|summaries| {
+                    let facets = facets.get();
+                    let mut items: Vec<InfoItem> = results
+                        .iter()
+                        .flat_map(|results| &results.items)
+                        .filter(|item| {
+                            let summary = summaries.get(&item.info_hash);
+                            let files = summary.map(|summary| FileFacts {
+                                num_files: summary.num_files,
+                                extensions: &summary.extensions,
+                            });
+                            let extensions = summary.map(|summary| &summary.extensions);
+                            query.matches(item, files, now) != Some(false)
+                                && facets.matches(extensions) != Some(false)
+                        })
+                        .cloned()
+                        .collect();
+                    if let Some(order) = sort() {
+                        sort_items(&mut items, order, summaries);
+                    }
+                    items
+                })
+            })
+        })
+    })
+    .into();
+    // Across everything loaded, so ticking a type doesn't make the others disappear.
+    let file_type_counts: Signal<Vec<(String, usize)>> = create_memo(cx, move |_| {
+        search_results.with(|results| {
+            file_summaries.with(|summaries| {
+                count_file_types(results.iter().flat_map(|results| &results.items), summaries)
+            })
+        })
+    })
+    .into();
+    let count = move || {
+        search_results.with(|results| {
+            results.as_ref().map(|results| {
+                let shown = items.with(Vec::len);
+                let hidden = results.items.len() - shown;
+                let mut count = format!("Showing {} of {}", shown, results.total);
+                if hidden > 0 {
+                    count += &format!(", {} hidden by filters", hidden);
+                }
+                count
+            })
+        })
+    };
+    let more_view = move || {
+        if loading_more() {
+            return Some(
+                view! { cx, <p><i class="fa fa-spinner fa-spin-pulse"></i></p> }.into_view(cx),
+            );
+        }
+        let error = load_more_error().map(|err| view_error(cx, &err));
+        search_results
+            .with(|results| {
+                results
+                    .as_ref()
+                    .map(SearchResults::has_more)
+                    .unwrap_or_default()
+            })
+            .then(|| {
+                view! { cx,
+                    {error}
+                    <button class="load-more" on:click=move |_| load_more()>
+                        "Load more"
+                    </button>
+                }
+                .into_view(cx)
+            })
+    };
     move || {
+        // Errors are returned so the ErrorBoundary shows them.
         herp.with(cx, |result| {
-            let Ok(Some(search_value)) = result else {
-                return None;
-            };
-            let search_value = search_value.clone();
-            Some(view! { cx,
-                <h3>{format!("Search results for {:?}", search_query())}</h3>
-                <TorrentsList search_value info_files_cache set_torrent_ih/>
+            result.as_ref().map_err(Clone::clone).map(|search| {
+                search.as_ref().map(|_| {
+                    view! { cx,
+                        <h3>{format!("Search results for {:?}", search_query())}</h3>
+                        <p>
+                            {count}
+                            " "
+                            <ExportResults items info_files_cache search_query/>
+                        </p>
+                        <div class="search-layout">
+                            <FileTypeFacetsSidebar counts=file_type_counts facets/>
+                            <div class="search-results">
+                                <TorrentsList items info_files_cache sort facets/>
+                                {more_view}
+                            </div>
+                        </div>
+                    }
+                })
             })
         })
     }
@@ -304,48 +826,146 @@ fn view_file_types(cx: Scope, file_types: impl IntoIterator<Item = String>) -> i
         .collect_view(cx)
 }
 
+// Clicking one narrows the results to that type.
+fn view_file_type_chips(
+    cx: Scope,
+    file_types: impl IntoIterator<Item = String>,
+    facets: RwSignal<FileTypeFacets>,
+) -> impl IntoView {
+    file_types
+        .into_iter()
+        .map(|file_type| {
+            let title = format!("Only show torrents with {} files", file_type);
+            let on_click = {
+                let file_type = file_type.clone();
+                move |_| facets.update(|facets| facets.set(&file_type, FacetState::Include))
+            };
+            view! { cx,
+                <button class="file-type" title=title on:click=on_click>
+                    {file_type}
+                </button>
+            }
+        })
+        .collect_view(cx)
+}
+
 #[component]
 fn TorrentsList(
     cx: Scope,
-    search_value: InfosSearch,
+    items: Signal<Vec<InfoItem>>,
     info_files_cache: ReadSignal<InfoFilesCache>,
-    set_torrent_ih: WriteSignal<Option<String>>,
+    sort: Memo<Option<SortOrder>>,
+    facets: RwSignal<FileTypeFacets>,
 ) -> impl IntoView {
+    let location = use_location(cx);
+    let refresh = use_context::<InfoFilesRefresh>(cx).expect("refresh to be provided");
+    let header = move |label: &'static str, column: SortColumn| {
+        let current = move || sort().filter(|order| order.column == column);
+        let aria_sort = move || match current() {
+            Some(order) if order.descending => "descending",
+            Some(_) => "ascending",
+            None => "none",
+        };
+        let indicator = move || {
+            current().map(|order| {
+                let icon = if order.descending {
+                    "fa fa-sort-down"
+                } else {
+                    "fa fa-sort-up"
+                };
+                view! { cx, " " <i class=icon></i> }
+            })
+        };
+        // Replaced rather than pushed, re-sorting isn't somewhere to go back to.
+        let on_click = move |_| {
+            let order = SortOrder::toggle(sort.get_untracked(), column).to_string();
+            let search = location.search.get_untracked();
+            navigate_to(
+                cx,
+                &format!(
+                    "/{}",
+                    with_query_param(&search, SORT_QUERY_PARAM, Some(&order))
+                ),
+                true,
+            );
+        };
+        view! { cx,
+            <th class="sortable" aria-sort=aria_sort on:click=on_click>
+                {label}
+                {indicator}
+            </th>
+        }
+    };
     let rows = move || {
         let cache = info_files_cache.get();
-        search_value
-            .clone()
-            .items
+        items
+            .get()
             .into_iter()
             .map(|torrent| {
-                let info_files: Option<&InfoFiles> = cache
-                    .get(&torrent.info_hash)
-                    .and_then(|value| value.as_ref().map(|result| result.as_ref().ok()))
-                    .flatten();
-                let loading =
-                    move || view! { cx, <i class="fa fa-spinner fa-spin-pulse"></i> }.into_view(cx);
+                let cached = cache.get(&torrent.info_hash).and_then(Option::as_ref);
+                let info_files: Option<&InfoFiles> = cached.and_then(|result| result.as_ref().ok());
+                let error = cached.and_then(|result| result.as_ref().err()).map(|err| {
+                    err.hint()
+                        .map(|hint| format!("{}. {}", err, hint))
+                        .unwrap_or_else(|| err.to_string())
+                });
+                let loading = || match &error {
+                    Some(error) => view! { cx,
+                        <i class="fa fa-triangle-exclamation" title=error.clone()></i>
+                    }
+                    .into_view(cx),
+                    None => view! { cx, <i class="fa fa-spinner fa-spin-pulse"></i> }.into_view(cx),
+                };
+                let info_hash = torrent.info_hash;
+                let retry = move || {
+                    view! { cx,
+                        " "
+                        <button
+                            class="retry"
+                            title="Try again"
+                            on:click=move |_| refresh.retry(info_hash)
+                        >
+                            <i class="fa fa-rotate-right"></i>
+                        </button>
+                    }
+                };
                 let num_files = info_files
                     .as_ref()
-                    .map(|info_files| info_files.files.len().into_view(cx))
-                    .unwrap_or_else(loading);
+                    .map(|info_files| info_files.files().len().into_view(cx))
+                    .unwrap_or_else(|| {
+                        view! { cx, {loading()} {error.is_some().then(retry)} }.into_view(cx)
+                    });
                 let file_types = info_files
                     .as_ref()
-                    .map(|info_files| view_file_types(cx, file_types(info_files)).into_view(cx))
+                    .map(|info_files| {
+                        view_file_type_chips(cx, file_types(info_files), facets).into_view(cx)
+                    })
                     .unwrap_or_else(loading);
-                let on_click = move |_| {
-                    info!("clicked {}", &torrent.info_hash);
-                    set_torrent_ih(Some(torrent.info_hash.clone()));
+                let version = match torrent.version() {
+                    TorrentVersion::V1 => None,
+                    version => {
+                        Some(view! { cx, <span class="badge version">{version.label()}</span> })
+                    }
                 };
+                // A real link, so it can be opened in a new tab. The router handles plain clicks.
+                let href = location.search.with(|search| {
+                    format!(
+                        "{}{}",
+                        torrent_path(&torrent.info_hash),
+                        query_string(search)
+                    )
+                });
                 view! { cx,
                     <tr>
                         <td class="name">
-                            <a href="#" on:click=on_click>
+                            <a href=href>
                                 {torrent.name}
                             </a>
+                            {version}
                         </td>
                         <td>{torrent.swarm_info.seeders}</td>
                         <td>{format_size(torrent.size, DECIMAL)}</td>
-                        <td>{torrent.age}</td>
+                        <td>{view_timestamp(cx, torrent.age)}</td>
                         <td>{num_files}</td>
                         <td>{file_types}</td>
                     </tr>
@@ -356,12 +976,12 @@ fn TorrentsList(
     view! { cx,
         <table>
             <tr>
-                <th>"Name"</th>
-                <th>"Seeders"</th>
-                <th>"Size"</th>
-                <th>"Age"</th>
-                <th>"Files"</th>
-                <th>"File Types"</th>
+                {header("Name", SortColumn::Name)}
+                {header("Seeders", SortColumn::Seeders)}
+                {header("Size", SortColumn::Size)}
+                {header("Age", SortColumn::Age)}
+                {header("Files", SortColumn::Files)}
+                {header("File Types", SortColumn::FileTypes)}
             </tr>
             {rows}
         </table>
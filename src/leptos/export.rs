@@ -0,0 +1,323 @@
+use super::*;
+use serde::Serialize;
+use wasm_bindgen::{JsCast, JsValue};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum ExportFormat {
+    Csv,
+    Json,
+    Ndjson,
+    Magnets,
+}
+
+impl ExportFormat {
+    pub(super) const ALL: [Self; 4] = [Self::Csv, Self::Json, Self::Ndjson, Self::Magnets];
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Self::Csv => "CSV",
+            Self::Json => "JSON",
+            Self::Ndjson => "NDJSON",
+            Self::Magnets => "Magnet links",
+        }
+    }
+
+    pub(super) fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+            Self::Magnets => "txt",
+        }
+    }
+
+    pub(super) fn mime_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+            Self::Magnets => "text/plain",
+        }
+    }
+
+    pub(super) fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == value)
+    }
+}
+
+// One search result as exported. The file fields are None when the info files weren't fetched (or
+// failed), rather than holding up the export for them.
+#[derive(Serialize, Debug)]
+struct ExportRecord {
+    info_hash: InfoHash,
+    info_hash_v2: Option<InfoHash>,
+    name: String,
+    size: u64,
+    seeders: u32,
+    leechers: u32,
+    completed: u32,
+    age: Option<String>,
+    num_files: Option<usize>,
+    file_types: Option<Vec<String>>,
+    magnet: String,
+}
+
+impl ExportRecord {
+    fn new(item: &InfoItem, info_files: Option<&InfoFiles>, trackers: &TrackerList) -> Self {
+        Self {
+            info_hash: item.info_hash,
+            info_hash_v2: item.info_hash_v2,
+            name: item.name.clone(),
+            size: item.size,
+            seeders: item.swarm_info.seeders,
+            leechers: item.swarm_info.leechers,
+            completed: item.swarm_info.completed,
+            age: item.age.datetime().map(|_| item.age.to_string()),
+            num_files: info_files.map(|info_files| info_files.files().len()),
+            file_types: info_files.map(file_types),
+            magnet: Magnet::new(item.info_hashes())
+                .with_display_name(item.name.clone())
+                .with_exact_length(item.size)
+                .with_trackers(trackers.0.iter().cloned())
+                .to_string(),
+        }
+    }
+
+    fn csv_fields(&self) -> [String; 11] {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        [
+            self.info_hash.to_string(),
+            optional(self.info_hash_v2.map(|info_hash| info_hash.to_string())),
+            self.name.clone(),
+            self.size.to_string(),
+            self.seeders.to_string(),
+            self.leechers.to_string(),
+            self.completed.to_string(),
+            optional(self.age.clone()),
+            optional(self.num_files.map(|num_files| num_files.to_string())),
+            optional(
+                self.file_types
+                    .as_ref()
+                    .map(|file_types| file_types.join(" ")),
+            ),
+            self.magnet.clone(),
+        ]
+    }
+}
+
+const CSV_HEADER: [&str; 11] = [
+    "info_hash",
+    "info_hash_v2",
+    "name",
+    "size",
+    "seeders",
+    "leechers",
+    "completed",
+    "age",
+    "num_files",
+    "file_types",
+    "magnet",
+];
+
+fn csv_field(field: &str) -> String {
+    // Names come from strangers, and spreadsheets run anything that looks like a formula.
+    let field = if field.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", field)
+    } else {
+        field.to_owned()
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+fn csv_line<S: AsRef<str>>(fields: impl IntoIterator<Item = S>) -> String {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|field| csv_field(field.as_ref()))
+        .collect();
+    fields.join(",") + "\r\n"
+}
+
+// The rows in the order they're shown. Only pages that have been loaded are included.
+pub(super) fn export_results(
+    format: ExportFormat,
+    items: &[InfoItem],
+    cache: &InfoFilesCache,
+    trackers: &TrackerList,
+) -> String {
+    let records = items.iter().map(|item| {
+        let info_files = cache
+            .get(&item.info_hash)
+            .and_then(|cached| cached.as_ref()?.as_ref().ok());
+        ExportRecord::new(item, info_files, trackers)
+    });
+    match format {
+        ExportFormat::Csv => std::iter::once(csv_line(CSV_HEADER))
+            .chain(records.map(|record| csv_line(record.csv_fields())))
+            .collect(),
+        ExportFormat::Json => {
+            serde_json::to_string_pretty(&records.collect::<Vec<_>>()).unwrap() + "\n"
+        }
+        ExportFormat::Ndjson => records
+            .map(|record| serde_json::to_string(&record).unwrap() + "\n")
+            .collect(),
+        ExportFormat::Magnets => records.map(|record| record.magnet + "\n").collect(),
+    }
+}
+
+fn download(contents: &str, filename: &str, mime_type: &str) -> std::result::Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(contents));
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(
+        &parts,
+        web_sys::BlobPropertyBag::new().type_(mime_type),
+    )?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+    let link: web_sys::HtmlAnchorElement = document().create_element("a")?.unchecked_into();
+    link.set_href(&url);
+    link.set_download(filename);
+    link.click();
+    // Some browsers, like Firefox, fetch the blob after click returns.
+    set_timeout(
+        move || {
+            let _ = web_sys::Url::revoke_object_url(&url);
+        },
+        std::time::Duration::from_secs(10),
+    );
+    Ok(())
+}
+
+// Something safe to save as, like "dhtsearch-big-buck-bunny.csv".
+fn export_filename(query: &str, format: ExportFormat) -> String {
+    let slug: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let slug = slug.join("-");
+    if slug.is_empty() {
+        format!("dhtsearch.{}", format.extension())
+    } else {
+        format!("dhtsearch-{}.{}", slug, format.extension())
+    }
+}
+
+#[component]
+pub(super) fn ExportResults(
+    cx: Scope,
+    items: Signal<Vec<InfoItem>>,
+    info_files_cache: ReadSignal<InfoFilesCache>,
+    search_query: Signal<String>,
+) -> impl IntoView {
+    let trackers = use_context::<RwSignal<TrackerList>>(cx).expect("trackers to be provided");
+    let format = create_rw_signal(cx, ExportFormat::Csv);
+    let on_format = move |ev| {
+        if let Some(value) = ExportFormat::parse(&event_target_value(&ev)) {
+            format.set(value);
+        }
+    };
+    let on_export = move |_| {
+        let format = format.get_untracked();
+        let contents = items.with_untracked(|items| {
+            info_files_cache.with_untracked(|cache| {
+                trackers.with_untracked(|trackers| export_results(format, items, cache, trackers))
+            })
+        });
+        let filename = export_filename(&search_query.get_untracked(), format);
+        if let Err(err) = download(&contents, &filename, format.mime_type()) {
+            warn!("exporting {}: {:?}", filename, err);
+        }
+    };
+    let options = ExportFormat::ALL
+        .into_iter()
+        .map(|value| {
+            view! { cx,
+                <option value=value.extension() selected=move || format() == value>
+                    {value.label()}
+                </option>
+            }
+        })
+        .collect_view(cx);
+    view! { cx,
+        <span class="export">
+            <select on:change=on_format>{options}</select>
+            <button on:click=on_export disabled=move || items.with(Vec::is_empty)>
+                <i class="fa fa-download"></i>
+                " Export"
+            </button>
+        </span>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str) -> InfoItem {
+        InfoItem {
+            info_hash: InfoHash::V1([1; 20]),
+            name: name.to_owned(),
+            size: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_csv() {
+        let items = [item("plain"), item("=HYPERLINK(\"x\"), then\nmore")];
+        let csv = export_results(
+            ExportFormat::Csv,
+            &items,
+            &Default::default(),
+            &TrackerList(vec![]),
+        );
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines[0], CSV_HEADER.join(","));
+        assert!(lines[1]
+            .starts_with("0101010101010101010101010101010101010101,,plain,10,0,0,0,,,,magnet:?"));
+        assert!(lines[2].contains(",,\"'=HYPERLINK(\"\"x\"\"), then\nmore\",10,"));
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn test_ndjson_and_magnets() {
+        let mut cache = InfoFilesCache::new();
+        cache.insert(
+            InfoHash::V1([1; 20]),
+            Some(Ok(InfoFiles {
+                files: vec![File {
+                    path: Some(vec!["a.mkv".into()]),
+                    length: 10,
+                }],
+                ..Default::default()
+            })),
+        );
+        let trackers = TrackerList(vec!["udp://t.example:1".to_owned()]);
+        let ndjson = export_results(ExportFormat::Ndjson, &[item("a")], &cache, &trackers);
+        let record: serde_json::Value = serde_json::from_str(ndjson.trim_end()).unwrap();
+        assert_eq!(
+            record["info_hash"],
+            "0101010101010101010101010101010101010101"
+        );
+        assert_eq!(record["num_files"], 1);
+        assert_eq!(record["file_types"], serde_json::json!(["mkv"]));
+        assert_eq!(
+            export_results(ExportFormat::Magnets, &[item("a")], &cache, &trackers),
+            "magnet:?xt=urn:btih:0101010101010101010101010101010101010101&dn=a&xl=10\
+             &tr=udp%3A%2F%2Ft.example%3A1\n"
+        );
+    }
+
+    #[test]
+    fn test_export_filename() {
+        assert_eq!(
+            export_filename("Big Buck  Bunny (2008)", ExportFormat::Ndjson),
+            "dhtsearch-big-buck-bunny-2008.ndjson"
+        );
+        assert_eq!(export_filename("", ExportFormat::Csv), "dhtsearch.csv");
+    }
+}
@@ -0,0 +1,258 @@
+use super::*;
+use std::collections::BTreeSet;
+
+// File types picked in the sidebar. A torrent has to have one of the included types, if there
+// are any, and none of the excluded ones. Types are lower case without the dot, and are checked
+// against every file's extension.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub(super) struct FileTypeFacets {
+    include: BTreeSet<String>,
+    exclude: BTreeSet<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum FacetState {
+    Include,
+    Exclude,
+    None,
+}
+
+impl FileTypeFacets {
+    pub(super) fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub(super) fn state(&self, file_type: &str) -> FacetState {
+        if self.include.contains(file_type) {
+            FacetState::Include
+        } else if self.exclude.contains(file_type) {
+            FacetState::Exclude
+        } else {
+            FacetState::None
+        }
+    }
+
+    pub(super) fn set(&mut self, file_type: &str, state: FacetState) {
+        self.include.remove(file_type);
+        self.exclude.remove(file_type);
+        match state {
+            FacetState::Include => self.include.insert(file_type.to_owned()),
+            FacetState::Exclude => self.exclude.insert(file_type.to_owned()),
+            FacetState::None => false,
+        };
+    }
+
+    // Every type that has a state, so they stay listed even if no loaded result has them.
+    fn file_types(&self) -> impl Iterator<Item = &String> {
+        self.include.iter().chain(&self.exclude)
+    }
+
+    // None until the torrent's file types are known.
+    pub(super) fn matches(&self, extensions: Option<&HashSet<String>>) -> Option<bool> {
+        if self.is_empty() {
+            return Some(true);
+        }
+        let extensions = extensions?;
+        let has = |set: &BTreeSet<String>| set.iter().any(|ext| extensions.contains(ext));
+        Some((self.include.is_empty() || has(&self.include)) && !has(&self.exclude))
+    }
+}
+
+// How many results have each file type, most common first.
+pub(super) fn count_file_types<'a>(
+    items: impl IntoIterator<Item = &'a InfoItem>,
+    summaries: &FileSummaries,
+) -> Vec<(String, usize)> {
+    let mut counts = HashMap::<String, usize>::new();
+    for item in items {
+        let Some(summary) = summaries.get(&item.info_hash) else {
+            continue;
+        };
+        for extension in &summary.extensions {
+            *counts.entry(extension.clone()).or_default() += 1;
+        }
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(left, left_count), (right, right_count)| {
+        right_count.cmp(left_count).then_with(|| left.cmp(right))
+    });
+    counts
+}
+
+#[component]
+pub(super) fn FileTypeFacetsSidebar(
+    cx: Scope,
+    counts: Signal<Vec<(String, usize)>>,
+    facets: RwSignal<FileTypeFacets>,
+) -> impl IntoView {
+    let rows = move || {
+        let mut rows = counts();
+        facets.with(|facets| {
+            for file_type in facets.file_types() {
+                if !rows.iter().any(|(listed, _)| listed == file_type) {
+                    rows.push((file_type.clone(), 0));
+                }
+            }
+        });
+        rows.into_iter()
+            .map(|(file_type, count)| {
+                let state = {
+                    let file_type = file_type.clone();
+                    move || facets.with(|facets| facets.state(&file_type))
+                };
+                let on_include = {
+                    let file_type = file_type.clone();
+                    move |ev| {
+                        let state = if event_target_checked(&ev) {
+                            FacetState::Include
+                        } else {
+                            FacetState::None
+                        };
+                        facets.update(|facets| facets.set(&file_type, state))
+                    }
+                };
+                let on_exclude = {
+                    let file_type = file_type.clone();
+                    move |_| {
+                        let state = match facets.with(|facets| facets.state(&file_type)) {
+                            FacetState::Exclude => FacetState::None,
+                            _ => FacetState::Exclude,
+                        };
+                        facets.update(|facets| facets.set(&file_type, state))
+                    }
+                };
+                view! { cx,
+                    <li class:excluded=move || state() == FacetState::Exclude>
+                        <label>
+                            <input
+                                type="checkbox"
+                                prop:checked=move || state() == FacetState::Include
+                                on:change=on_include
+                            />
+                            " "
+                            <span class="file-type">{file_type.clone()}</span>
+                            {format!(" {}", count)}
+                        </label>
+                        <button
+                            class="exclude"
+                            title=format!("Hide torrents with {} files", file_type)
+                            on:click=on_exclude
+                        >
+                            <i class="fa fa-ban"></i>
+                        </button>
+                    </li>
+                }
+            })
+            .collect_view(cx)
+    };
+    let clear = move || {
+        (!facets.with(FileTypeFacets::is_empty)).then(|| {
+            view! { cx,
+                <button on:click=move |_| facets.set(Default::default())>"Clear"</button>
+            }
+        })
+    };
+    view! { cx,
+        <aside class="facets">
+            <h4>"File Types" {clear}</h4>
+            <ul>{rows}</ul>
+        </aside>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(file_types: &[&str]) -> HashSet<String> {
+        file_types
+            .iter()
+            .map(|&file_type| file_type.to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut facets = FileTypeFacets::default();
+        assert_eq!(facets.matches(None), Some(true));
+        facets.set("mkv", FacetState::Include);
+        facets.set("mp4", FacetState::Include);
+        facets.set("exe", FacetState::Exclude);
+        assert_eq!(facets.matches(None), None);
+        assert_eq!(facets.matches(Some(&types(&["mkv", "srt"]))), Some(true));
+        assert_eq!(facets.matches(Some(&types(&["mkv", "exe"]))), Some(false));
+        assert_eq!(facets.matches(Some(&types(&["avi"]))), Some(false));
+        // Moving a type from include to exclude.
+        facets.set("mkv", FacetState::Exclude);
+        assert_eq!(facets.state("mkv"), FacetState::Exclude);
+        assert_eq!(facets.matches(Some(&types(&["mp4", "mkv"]))), Some(false));
+        facets.set("mp4", FacetState::None);
+        assert_eq!(facets.matches(Some(&types(&["avi"]))), Some(true));
+    }
+
+    #[test]
+    fn test_count_file_types() {
+        let item = |byte| InfoItem {
+            info_hash: InfoHash::V1([byte; 20]),
+            ..Default::default()
+        };
+        let info_files = |names: &[&str]| InfoFiles {
+            files: names
+                .iter()
+                .map(|&name| File {
+                    path: Some(vec![name.into()]),
+                    length: 1,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let mut cache = InfoFilesCache::new();
+        cache.insert(
+            InfoHash::V1([1; 20]),
+            Some(Ok(info_files(&["a.mkv", "a.srt"]))),
+        );
+        cache.insert(InfoHash::V1([2; 20]), Some(Ok(info_files(&["b.MKV"]))));
+        cache.insert(InfoHash::V1([3; 20]), None);
+        let items = [item(1), item(2), item(3), item(4)];
+        let summaries = update_file_summaries(None, &cache);
+        assert_eq!(
+            count_file_types(&items, &summaries),
+            [("mkv".to_owned(), 2), ("srt".to_owned(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_facets_see_every_extension() {
+        // The subtitles and the installer are the smallest, so file_types leaves them out.
+        let names = [
+            "a.mkv", "b.mp4", "c.avi", "d.flac", "e.iso", "f.zip", "g.rar", "h.srt", "i.exe",
+        ];
+        let info_files = InfoFiles {
+            files: names
+                .iter()
+                .enumerate()
+                .map(|(index, &name)| File {
+                    path: Some(vec![name.into()]),
+                    length: 100 - index as FileLength,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        assert!(!file_types(&info_files).contains(&"srt".to_owned()));
+        let mut cache = InfoFilesCache::new();
+        cache.insert(InfoHash::V1([1; 20]), Some(Ok(info_files)));
+        let summaries = update_file_summaries(None, &cache);
+        let extensions = Some(&summaries[&InfoHash::V1([1; 20])].extensions);
+        let mut facets = FileTypeFacets::default();
+        facets.set("exe", FacetState::Exclude);
+        assert_eq!(facets.matches(extensions), Some(false));
+        facets.set("exe", FacetState::None);
+        facets.set("srt", FacetState::Include);
+        assert_eq!(facets.matches(extensions), Some(true));
+        let item = InfoItem {
+            info_hash: InfoHash::V1([1; 20]),
+            ..Default::default()
+        };
+        assert_eq!(count_file_types([&item], &summaries).len(), names.len());
+    }
+}
@@ -0,0 +1,91 @@
+use super::*;
+use crate::metainfo::Metainfo;
+use ::leptos::html::Input;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::DragEvent;
+
+// Opens a .torrent from disk without going through the indexer. on_import gets whether the user
+// wants the indexer asked about it anyway.
+#[component]
+pub(super) fn ImportTorrent<F>(cx: Scope, on_import: F) -> impl IntoView
+where
+    F: Fn(Metainfo, bool) + Copy + 'static,
+{
+    let input_element: NodeRef<Input> = create_node_ref(cx);
+    let lookup = create_rw_signal(cx, true);
+    let dragging = create_rw_signal(cx, false);
+    let error = create_rw_signal(cx, None::<String>);
+    let import = move |file: web_sys::File| {
+        error.set(None);
+        spawn_local(async move {
+            match read_torrent_file(&file).await {
+                Ok(metainfo) => on_import(metainfo, lookup.get_untracked()),
+                Err(err) => error.set(Some(format!("{}: {}", file.name(), err))),
+            }
+        })
+    };
+    let on_change = move |_| {
+        let input = input_element().expect("<input> to exist");
+        if let Some(file) = input.files().and_then(|files| files.get(0)) {
+            import(file);
+        }
+        // Otherwise picking the same file again doesn't fire change.
+        input.set_value("");
+    };
+    let on_dragover = move |ev: DragEvent| {
+        // Needed for the browser to allow a drop here.
+        ev.prevent_default();
+        dragging.set(true);
+    };
+    let on_drop = move |ev: DragEvent| {
+        ev.prevent_default();
+        dragging.set(false);
+        if let Some(file) = ev
+            .data_transfer()
+            .and_then(|data_transfer| data_transfer.files())
+            .and_then(|files| files.get(0))
+        {
+            import(file);
+        }
+    };
+    let error_view = move || error().map(|error| view! { cx, <p class="error">{error}</p> });
+    view! { cx,
+        <div
+            class="import-torrent"
+            class:dragging=move || dragging()
+            on:dragover=on_dragover
+            on:dragleave=move |_| dragging.set(false)
+            on:drop=on_drop
+        >
+            <label>
+                <i class="fa fa-folder-open"></i>
+                " Open .torrent file "
+                <input
+                    type="file"
+                    accept=".torrent,application/x-bittorrent"
+                    node_ref=input_element
+                    on:change=on_change
+                />
+            </label>
+            " or drop one here. "
+            <label>
+                <input
+                    type="checkbox"
+                    prop:checked=move || lookup()
+                    on:change=move |ev| lookup.set(event_target_checked(&ev))
+                />
+                " Look up swarm info on the indexer"
+            </label>
+            {error_view}
+        </div>
+    }
+}
+
+async fn read_torrent_file(file: &web_sys::File) -> Result<Metainfo> {
+    let buffer = JsFuture::from(file.array_buffer())
+        .await
+        .map_err(|err| anyhow!("reading file: {:?}", err))?;
+    Ok(Metainfo::from_bytes(
+        &js_sys::Uint8Array::new(&buffer).to_vec(),
+    )?)
+}
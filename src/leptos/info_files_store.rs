@@ -0,0 +1,564 @@
+use super::*;
+use crate::api::local_storage;
+use futures::future::{select, try_join_all, Either, LocalBoxFuture};
+use futures::FutureExt;
+use js_sys::{Array, Date, Promise};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::future::Future;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbOpenDbRequest, IdbRequest, IdbTransaction, IdbTransactionMode};
+
+const DB_NAME: &str = "dhtsearch";
+// Bump when what's stored changes shape. Anything saved under an older version is dropped rather
+// than read.
+const SCHEMA_VERSION: u32 = 1;
+// Info files as the indexer's JSON, keyed by info hash.
+const INFO_FILES_STORE: &str = "info_files";
+// The size and times for each saved torrent. Small enough to load whole when the page opens, the
+// info files themselves are only read when they're wanted.
+const ENTRIES_STORE: &str = "entries";
+const SETTINGS_STORAGE_KEY: &str = "info_files_cache";
+const MILLIS_PER_DAY: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+// How long to wait for another tab to let go of an older version before going without the cache.
+const OPEN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+pub(super) type InfoFilesStoreResource = Resource<(), Option<InfoFilesStore>>;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub(super) struct CacheSettings {
+    pub budget_mb: u32,
+    pub ttl_days: u32,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            budget_mb: 50,
+            ttl_days: 7,
+        }
+    }
+}
+
+impl CacheSettings {
+    pub(super) fn load() -> Self {
+        local_storage()
+            .and_then(|storage| storage.get_item(SETTINGS_STORAGE_KEY).ok().flatten())
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn save(self) {
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        let value = serde_json::to_string(&self).unwrap();
+        if let Err(err) = storage.set_item(SETTINGS_STORAGE_KEY, &value) {
+            warn!("saving info files cache settings: {:?}", err);
+        }
+    }
+
+    fn budget_bytes(self) -> u64 {
+        u64::from(self.budget_mb) * 1_000_000
+    }
+
+    fn ttl_millis(self) -> f64 {
+        f64::from(self.ttl_days) * MILLIS_PER_DAY
+    }
+}
+
+// Times are milliseconds since the epoch, as Date.now() gives them.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+struct Entry {
+    info_hash: InfoHash,
+    size: u64,
+    stored_at: f64,
+    last_used: f64,
+    // An imported torrent's own trackers, which the indexer doesn't have.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    trackers: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub(super) struct CacheUsage {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+#[derive(Default, Debug)]
+struct CacheIndex {
+    entries: HashMap<InfoHash, Entry>,
+}
+
+impl CacheIndex {
+    fn usage(&self) -> CacheUsage {
+        CacheUsage {
+            entries: self.entries.len(),
+            bytes: self.entries.values().map(|entry| entry.size).sum(),
+        }
+    }
+
+    fn is_fresh(&self, info_hash: &InfoHash, settings: CacheSettings, now: f64) -> bool {
+        self.entries
+            .get(info_hash)
+            .is_some_and(|entry| now - entry.stored_at < settings.ttl_millis())
+    }
+
+    // Anything past its TTL, then the least recently used until the rest fits the budget.
+    fn evictions(&self, settings: CacheSettings, now: f64) -> Vec<InfoHash> {
+        let (mut evict, mut keep): (Vec<&Entry>, Vec<&Entry>) = self
+            .entries
+            .values()
+            .partition(|entry| now - entry.stored_at >= settings.ttl_millis());
+        keep.sort_by(|left, right| left.last_used.total_cmp(&right.last_used));
+        let mut bytes: u64 = keep.iter().map(|entry| entry.size).sum();
+        for entry in keep {
+            if bytes <= settings.budget_bytes() {
+                break;
+            }
+            bytes -= entry.size;
+            evict.push(entry);
+        }
+        evict.into_iter().map(|entry| entry.info_hash).collect()
+    }
+}
+
+// Resolves with the request's result. The handlers are attached straight away, before the
+// returned future is polled, so none of the request's events are missed.
+fn request_result(request: &IdbRequest) -> impl Future<Output = Result<JsValue, JsValue>> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    let request = request.clone();
+    async move {
+        JsFuture::from(promise).await?;
+        request.result()
+    }
+}
+
+fn key(info_hash: &InfoHash) -> JsValue {
+    JsValue::from_str(&info_hash.to_string())
+}
+
+fn json_error(err: serde_json::Error) -> JsValue {
+    JsValue::from_str(&err.to_string())
+}
+
+// Like request_result, but a tab holding an older version open blocks it, possibly forever.
+async fn open_result(request: &IdbOpenDbRequest) -> Result<IdbDatabase, JsValue> {
+    let opened = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+        request.set_onblocked(Some(&reject));
+    });
+    let timeout = futures_timer::Delay::new(OPEN_TIMEOUT);
+    let result = match select(JsFuture::from(opened), timeout).await {
+        Either::Left((Ok(_), _)) => return Ok(request.result()?.unchecked_into()),
+        Either::Left((Err(err), _)) => Err(err),
+        Either::Right(_) => Err(JsValue::from_str("timed out opening IndexedDB")),
+    };
+    // If it opens after all, it's closed again so it doesn't hold up anyone else.
+    let on_success = {
+        let request = request.clone();
+        Closure::once_into_js(move |_: web_sys::Event| {
+            if let Ok(db) = request.result() {
+                db.unchecked_into::<IdbDatabase>().close();
+            }
+        })
+    };
+    request.set_onsuccess(Some(on_success.unchecked_ref()));
+    result
+}
+
+// Stores from older schema versions are replaced, not migrated.
+fn upgrade(request: &IdbOpenDbRequest) -> Result<(), JsValue> {
+    let db: IdbDatabase = request.result()?.unchecked_into();
+    let existing = db.object_store_names();
+    for name in [INFO_FILES_STORE, ENTRIES_STORE] {
+        if existing.contains(name) {
+            db.delete_object_store(name)?;
+        }
+        db.create_object_store(name)?;
+    }
+    Ok(())
+}
+
+// Info files saved in IndexedDB, so they survive a reload and don't have to be fetched again.
+// Only what came from the indexer is saved, imported torrents aren't unless they're looked up,
+// and then their own trackers are saved with them.
+#[derive(Clone)]
+pub(super) struct InfoFilesStore {
+    db: IdbDatabase,
+    index: Rc<RefCell<CacheIndex>>,
+    usage: RwSignal<CacheUsage>,
+}
+
+impl InfoFilesStore {
+    pub(super) async fn open(usage: RwSignal<CacheUsage>) -> Result<Self, JsValue> {
+        let factory = web_sys::window()
+            .ok_or_else(|| JsValue::from_str("no window"))?
+            .indexed_db()?
+            .ok_or_else(|| JsValue::from_str("IndexedDB isn't available"))?;
+        let request = factory.open_with_u32(DB_NAME, SCHEMA_VERSION)?;
+        let on_upgrade = {
+            let request = request.clone();
+            Closure::once_into_js(move |_: web_sys::Event| {
+                if let Err(err) = upgrade(&request) {
+                    warn!("upgrading info files cache: {:?}", err);
+                }
+            })
+        };
+        request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+        let db = open_result(&request).await?;
+        // A newer version being opened in another tab waits for this one to close. Reads and
+        // writes fail after that, and are done without.
+        let on_version_change = {
+            let db = db.clone();
+            Closure::once_into_js(move |_: web_sys::Event| {
+                info!("closing info files cache for a newer version");
+                db.close();
+            })
+        };
+        db.set_onversionchange(Some(on_version_change.unchecked_ref()));
+        let store = Self {
+            db,
+            index: Default::default(),
+            usage,
+        };
+        store.load_index().await?;
+        store.evict().await?;
+        Ok(store)
+    }
+
+    fn transaction(&self, mode: IdbTransactionMode) -> Result<IdbTransaction, JsValue> {
+        let names = Array::of2(
+            &JsValue::from_str(INFO_FILES_STORE),
+            &JsValue::from_str(ENTRIES_STORE),
+        );
+        self.db.transaction_with_str_sequence_and_mode(&names, mode)
+    }
+
+    async fn load_index(&self) -> Result<(), JsValue> {
+        let transaction = self.transaction(IdbTransactionMode::Readonly)?;
+        let values = request_result(&transaction.object_store(ENTRIES_STORE)?.get_all()?).await?;
+        let entries = values
+            .unchecked_into::<Array>()
+            .iter()
+            .filter_map(|value| serde_json::from_str::<Entry>(&value.as_string()?).ok())
+            .map(|entry| (entry.info_hash, entry));
+        self.index.borrow_mut().entries.extend(entries);
+        Ok(())
+    }
+
+    async fn put_entries(&self, entries: Vec<Entry>) -> Result<(), JsValue> {
+        let transaction = self.transaction(IdbTransactionMode::Readwrite)?;
+        let store = transaction.object_store(ENTRIES_STORE)?;
+        let mut requests = vec![];
+        for entry in &entries {
+            let value = serde_json::to_string(entry).map_err(json_error)?;
+            requests.push(request_result(
+                &store.put_with_key(&JsValue::from_str(&value), &key(&entry.info_hash))?,
+            ));
+        }
+        try_join_all(requests).await?;
+        let mut index = self.index.borrow_mut();
+        for entry in entries {
+            index.entries.insert(entry.info_hash, entry);
+        }
+        Ok(())
+    }
+
+    // The ones that are saved and haven't expired.
+    pub(super) async fn get_many(&self, info_hashes: &[InfoHash]) -> Vec<InfoFiles> {
+        self.try_get_many(info_hashes).await.unwrap_or_else(|err| {
+            warn!("reading saved info files: {:?}", err);
+            vec![]
+        })
+    }
+
+    async fn try_get_many(&self, info_hashes: &[InfoHash]) -> Result<Vec<InfoFiles>, JsValue> {
+        let settings = CacheSettings::load();
+        let now = Date::now();
+        let wanted: Vec<InfoHash> = {
+            let index = self.index.borrow();
+            info_hashes
+                .iter()
+                .filter(|info_hash| index.is_fresh(info_hash, settings, now))
+                .copied()
+                .collect()
+        };
+        if wanted.is_empty() {
+            return Ok(vec![]);
+        }
+        let transaction = self.transaction(IdbTransactionMode::Readonly)?;
+        let store = transaction.object_store(INFO_FILES_STORE)?;
+        let requests = wanted
+            .iter()
+            .map(|info_hash| Ok(request_result(&store.get(&key(info_hash))?)))
+            .collect::<Result<Vec<_>, JsValue>>()?;
+        let mut found = vec![];
+        for value in try_join_all(requests).await? {
+            let Some(json) = value.as_string() else {
+                continue;
+            };
+            match serde_json::from_str::<InfoFiles>(&json) {
+                Ok(info_files) => found.push(info_files),
+                Err(err) => warn!("decoding saved info files: {}", err),
+            }
+        }
+        let used: Vec<Entry> = {
+            let index = self.index.borrow();
+            found
+                .iter()
+                .filter_map(|info_files| index.entries.get(&info_files.info.info_hash))
+                .map(|entry| Entry {
+                    last_used: now,
+                    ..entry.clone()
+                })
+                .collect()
+        };
+        self.put_entries(used).await?;
+        Ok(found)
+    }
+
+    // Anything not in own_trackers keeps the trackers it was saved with before.
+    pub(super) async fn put_many(&self, payload: &[InfoFiles], own_trackers: &ImportedTrackers) {
+        if let Err(err) = self.try_put_many(payload, own_trackers).await {
+            warn!("saving info files: {:?}", err);
+        }
+    }
+
+    async fn try_put_many(
+        &self,
+        payload: &[InfoFiles],
+        own_trackers: &ImportedTrackers,
+    ) -> Result<(), JsValue> {
+        let now = Date::now();
+        let transaction = self.transaction(IdbTransactionMode::Readwrite)?;
+        let store = transaction.object_store(INFO_FILES_STORE)?;
+        let mut requests = vec![];
+        let mut entries = vec![];
+        for info_files in payload {
+            let json = serde_json::to_string(info_files).map_err(json_error)?;
+            let info_hash = info_files.info.info_hash;
+            requests.push(request_result(
+                &store.put_with_key(&JsValue::from_str(&json), &key(&info_hash))?,
+            ));
+            let trackers = match own_trackers.get(&info_hash) {
+                Some(trackers) => trackers.clone(),
+                None => self
+                    .index
+                    .borrow()
+                    .entries
+                    .get(&info_hash)
+                    .map(|entry| entry.trackers.clone())
+                    .unwrap_or_default(),
+            };
+            entries.push(Entry {
+                info_hash,
+                size: json.len() as u64,
+                stored_at: now,
+                last_used: now,
+                trackers,
+            });
+        }
+        try_join_all(requests).await?;
+        self.put_entries(entries).await?;
+        self.evict().await
+    }
+
+    // Brings it back within the current settings.
+    pub(super) async fn evict(&self) -> Result<(), JsValue> {
+        let evictions = self
+            .index
+            .borrow()
+            .evictions(CacheSettings::load(), Date::now());
+        if !evictions.is_empty() {
+            debug!("evicting {} saved info files", evictions.len());
+            let transaction = self.transaction(IdbTransactionMode::Readwrite)?;
+            let mut requests = vec![];
+            for name in [INFO_FILES_STORE, ENTRIES_STORE] {
+                let store = transaction.object_store(name)?;
+                for info_hash in &evictions {
+                    requests.push(request_result(&store.delete(&key(info_hash))?));
+                }
+            }
+            try_join_all(requests).await?;
+            let mut index = self.index.borrow_mut();
+            for info_hash in &evictions {
+                index.entries.remove(info_hash);
+            }
+        }
+        self.update_usage();
+        Ok(())
+    }
+
+    pub(super) async fn clear(&self) -> Result<(), JsValue> {
+        let transaction = self.transaction(IdbTransactionMode::Readwrite)?;
+        let requests = [INFO_FILES_STORE, ENTRIES_STORE]
+            .into_iter()
+            .map(|name| Ok(request_result(&transaction.object_store(name)?.clear()?)))
+            .collect::<Result<Vec<_>, JsValue>>()?;
+        try_join_all(requests).await?;
+        self.index.borrow_mut().entries.clear();
+        self.update_usage();
+        Ok(())
+    }
+
+    // For the magnet links of imported torrents that were saved.
+    pub(super) fn imported_trackers(&self) -> ImportedTrackers {
+        self.index
+            .borrow()
+            .entries
+            .values()
+            .filter(|entry| !entry.trackers.is_empty())
+            .map(|entry| (entry.info_hash, entry.trackers.clone()))
+            .collect()
+    }
+
+    fn update_usage(&self) {
+        self.usage.set(self.index.borrow().usage());
+    }
+}
+
+#[component]
+pub(super) fn InfoFilesCacheSettings(cx: Scope) -> impl IntoView {
+    let store = use_context::<InfoFilesStoreResource>(cx).expect("info files store to be provided");
+    let usage = use_context::<RwSignal<CacheUsage>>(cx).expect("cache usage to be provided");
+    let settings = create_rw_signal(cx, CacheSettings::load());
+    let run =
+        move |action: &'static str,
+              run: fn(InfoFilesStore) -> LocalBoxFuture<'static, Result<(), JsValue>>| {
+            let Some(Some(store)) = store.read(cx) else {
+                return;
+            };
+            spawn_local(async move {
+                if let Err(err) = run(store).await {
+                    warn!("{} info files cache: {:?}", action, err);
+                }
+            });
+        };
+    let update = move |change: fn(&mut CacheSettings, u32), ev: web_sys::Event| {
+        let Ok(value) = event_target_value(&ev).parse() else {
+            return;
+        };
+        settings.update(|settings| change(settings, value));
+        settings.get_untracked().save();
+        run("evicting from", |store| {
+            async move { store.evict().await }.boxed_local()
+        });
+    };
+    let on_budget = move |ev| update(|settings, value| settings.budget_mb = value, ev);
+    let on_ttl = move |ev| update(|settings, value| settings.ttl_days = value, ev);
+    let on_clear = move |_| {
+        run("clearing", |store| {
+            async move { store.clear().await }.boxed_local()
+        })
+    };
+    let summary = move || {
+        let CacheUsage { entries, bytes } = usage();
+        let budget = settings.with(|settings| settings.budget_bytes());
+        match store.read(cx) {
+            Some(Some(_)) => format!(
+                "{} torrents saved, {} of {}",
+                entries,
+                format_size(bytes),
+                format_size(budget)
+            ),
+            Some(None) => "Not available in this browser".to_owned(),
+            None => "Opening...".to_owned(),
+        }
+    };
+    view! { cx,
+        <fieldset class="cache-settings">
+            <legend>"Saved file lists"</legend>
+            <p>{summary}</p>
+            <label>
+                "Up to "
+                <input
+                    type="number"
+                    min="0"
+                    prop:value=move || settings.with(|settings| settings.budget_mb)
+                    on:change=on_budget
+                />
+                " MB"
+            </label>
+            <label>
+                " for "
+                <input
+                    type="number"
+                    min="0"
+                    prop:value=move || settings.with(|settings| settings.ttl_days)
+                    on:change=on_ttl
+                />
+                " days"
+            </label>
+            <button type="button" on:click=on_clear>
+                "Clear"
+            </button>
+        </fieldset>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(byte: u8, size: u64, stored_at: f64, last_used: f64) -> Entry {
+        Entry {
+            info_hash: InfoHash::V1([byte; 20]),
+            size,
+            stored_at,
+            last_used,
+            trackers: vec![],
+        }
+    }
+
+    fn index(entries: impl IntoIterator<Item = Entry>) -> CacheIndex {
+        CacheIndex {
+            entries: entries
+                .into_iter()
+                .map(|entry| (entry.info_hash, entry))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_evictions() {
+        let settings = CacheSettings {
+            budget_mb: 1,
+            ttl_days: 1,
+        };
+        let now = 10.0 * MILLIS_PER_DAY;
+        let index = index([
+            entry(1, 400_000, now - 2.0 * MILLIS_PER_DAY, now),
+            entry(2, 400_000, now, now - 3.0),
+            entry(3, 400_000, now, now - 2.0),
+            entry(4, 400_000, now, now - 1.0),
+        ]);
+        let mut evictions = index.evictions(settings, now);
+        evictions.sort();
+        // The expired one, then the least recently used until 800 KB are left.
+        assert_eq!(evictions, [InfoHash::V1([1; 20]), InfoHash::V1([2; 20])]);
+        assert!(!index.is_fresh(&InfoHash::V1([1; 20]), settings, now));
+        assert!(index.is_fresh(&InfoHash::V1([4; 20]), settings, now));
+        assert_eq!(
+            index.usage(),
+            CacheUsage {
+                entries: 4,
+                bytes: 1_600_000
+            }
+        );
+    }
+
+    #[test]
+    fn test_evictions_within_budget() {
+        let index = index([entry(1, 10, 0.0, 0.0)]);
+        assert!(index
+            .evictions(CacheSettings::default(), MILLIS_PER_DAY)
+            .is_empty());
+    }
+}
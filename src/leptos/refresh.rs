@@ -0,0 +1,294 @@
+use super::*;
+use chrono::{DateTime, Duration, Utc};
+use info_files_store::InfoFilesStore;
+
+// Slower than the transport's own retries, these are for things like the indexer not having the
+// torrent yet rather than a dropped connection.
+fn auto_retry_policy() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 4,
+        base_delay: std::time::Duration::from_secs(5),
+        max_delay: std::time::Duration::from_secs(120),
+        jitter: true,
+    }
+}
+
+// Swarm info scraped longer ago than this is refreshed in the background when its torrent is
+// opened.
+const SCRAPE_MAX_AGE_MINUTES: i64 = 60;
+// At most this often for each torrent, since the indexer may not have scraped it since either.
+const REVALIDATE_INTERVAL_MINUTES: i64 = 10;
+
+// Torrents that were never scraped count as stale.
+fn needs_revalidation(
+    info: &Info,
+    last_revalidated: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> bool {
+    let stale = info
+        .scrape_time
+        .age(now)
+        .map_or(true, |age| age > Duration::minutes(SCRAPE_MAX_AGE_MINUTES));
+    stale
+        && last_revalidated.map_or(true, |at| {
+            now - at > Duration::minutes(REVALIDATE_INTERVAL_MINUTES)
+        })
+}
+
+enum Cached {
+    Missing,
+    Ok,
+    Failed,
+}
+
+fn cached(cache: &InfoFilesCache, info_hash: &InfoHash) -> Cached {
+    match cache.get(info_hash) {
+        Some(Some(Ok(_))) => Cached::Ok,
+        Some(Some(Err(_))) => Cached::Failed,
+        _ => Cached::Missing,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum AfterFetch {
+    // It's as fresh as the indexer has.
+    Fetched,
+    Retry(std::time::Duration),
+    // Cancelled, the fetch effect deals with those, or failed too many times in a row.
+    Nothing,
+}
+
+// Counts failures in a row for a fetched torrent, and decides what happens to it next.
+fn after_fetch(
+    failures: &mut HashMap<InfoHash, u32>,
+    info_hash: InfoHash,
+    cached: Cached,
+    policy: &RetryPolicy,
+) -> AfterFetch {
+    match cached {
+        Cached::Ok => {
+            failures.remove(&info_hash);
+            AfterFetch::Fetched
+        }
+        Cached::Missing => AfterFetch::Nothing,
+        Cached::Failed => {
+            let attempts = failures.entry(info_hash).or_default();
+            *attempts += 1;
+            if *attempts < policy.max_attempts {
+                AfterFetch::Retry(policy.backoff(*attempts))
+            } else {
+                AfterFetch::Nothing
+            }
+        }
+    }
+}
+
+// Keeps the info files cache from holding onto failures and old swarm info for the whole session.
+#[derive(Clone, Copy)]
+pub(super) struct InfoFilesRefresh {
+    cache: RwSignal<InfoFilesCache>,
+    // Fetched or being fetched, so the fetch effect doesn't ask twice.
+    pub attempted: StoredValue<HashSet<InfoHash>>,
+    // Failed fetches in a row.
+    failures: StoredValue<HashMap<InfoHash, u32>>,
+    revalidated: StoredValue<HashMap<InfoHash, DateTime<Utc>>>,
+    // Imported without asking to look them up, so they're left alone.
+    not_looked_up: StoredValue<HashSet<InfoHash>>,
+}
+
+impl InfoFilesRefresh {
+    pub(super) fn new(cx: Scope, cache: RwSignal<InfoFilesCache>) -> Self {
+        Self {
+            cache,
+            attempted: store_value(cx, Default::default()),
+            failures: store_value(cx, Default::default()),
+            revalidated: store_value(cx, Default::default()),
+            not_looked_up: store_value(cx, Default::default()),
+        }
+    }
+
+    // Imports have never been scraped, so looking one up is left to revalidate.
+    pub(super) fn imported(self, info_hash: InfoHash, lookup: bool) {
+        self.not_looked_up.update_value(|not_looked_up| {
+            if lookup {
+                not_looked_up.remove(&info_hash);
+            } else {
+                not_looked_up.insert(info_hash);
+            }
+        });
+        self.revalidated.update_value(|revalidated| {
+            revalidated.remove(&info_hash);
+        });
+    }
+
+    // The fetch effect asks for it again, if anything still wants it.
+    fn forget(self, info_hash: InfoHash) {
+        self.attempted.update_value(|attempted| {
+            attempted.remove(&info_hash);
+        });
+        self.cache.update(|cache| {
+            cache.remove(&info_hash);
+        });
+    }
+
+    // For the retry button. The backoff starts over.
+    pub(super) fn retry(self, info_hash: InfoHash) {
+        self.failures.update_value(|failures| {
+            failures.remove(&info_hash);
+        });
+        self.forget(info_hash);
+    }
+
+    // Called with what was asked of the indexer once the fetch is done. Failures are tried again
+    // after a backoff, a few times.
+    pub(super) fn fetched(self, info_hashes: &[InfoHash]) {
+        let policy = auto_retry_policy();
+        let now = Utc::now();
+        for &info_hash in info_hashes {
+            let state = self.cache.with_untracked(|cache| cached(cache, &info_hash));
+            let mut next = AfterFetch::Nothing;
+            self.failures.update_value(|failures| {
+                next = after_fetch(failures, info_hash, state, &policy);
+            });
+            match next {
+                AfterFetch::Fetched => {
+                    self.revalidated.update_value(|revalidated| {
+                        revalidated.insert(info_hash, now);
+                    });
+                }
+                AfterFetch::Retry(delay) => {
+                    debug!("retrying info files for {} in {:?}", info_hash, delay);
+                    spawn_local(async move {
+                        futures_timer::Delay::new(delay).await;
+                        // It might have been retried by hand meanwhile.
+                        let failed = self.cache.with_untracked(|cache| {
+                            matches!(cached(cache, &info_hash), Cached::Failed)
+                        });
+                        if failed {
+                            self.forget(info_hash);
+                        }
+                    });
+                }
+                AfterFetch::Nothing => {}
+            }
+        }
+    }
+
+    // Stale-while-revalidate: the cached swarm info stays on screen while fresher is fetched.
+    pub(super) fn revalidate(
+        self,
+        indexer: Indexer,
+        store: Option<InfoFilesStore>,
+        info_hash: InfoHash,
+        own_trackers: Option<Vec<String>>,
+    ) {
+        let now = Utc::now();
+        let last_revalidated = self
+            .revalidated
+            .with_value(|revalidated| revalidated.get(&info_hash).copied());
+        let stale = self
+            .cache
+            .with_untracked(|cache| match cache.get(&info_hash) {
+                Some(Some(Ok(info_files))) => {
+                    needs_revalidation(&info_files.info, last_revalidated, now)
+                }
+                _ => false,
+            });
+        if !stale
+            || self
+                .not_looked_up
+                .with_value(|skip| skip.contains(&info_hash))
+        {
+            return;
+        }
+        self.revalidated.update_value(|revalidated| {
+            revalidated.insert(info_hash, now);
+        });
+        debug!("refreshing swarm info for {}", info_hash);
+        spawn_local(async move {
+            let Some(updated) = merge_indexed_swarm_info(&indexer, self.cache, info_hash).await
+            else {
+                return;
+            };
+            if let Some(store) = store {
+                let own_trackers = own_trackers
+                    .map(|trackers| (info_hash, trackers))
+                    .into_iter()
+                    .collect();
+                store.put_many(&[updated], &own_trackers).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_after_fetch() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..auto_retry_policy()
+        };
+        let info_hash = InfoHash::V1([1; 20]);
+        let mut failures = HashMap::new();
+        let failed = |failures: &mut HashMap<InfoHash, u32>| {
+            after_fetch(failures, info_hash, Cached::Failed, &policy)
+        };
+        assert_eq!(failed(&mut failures), AfterFetch::Retry(policy.base_delay));
+        assert_eq!(
+            failed(&mut failures),
+            AfterFetch::Retry(policy.base_delay * 2)
+        );
+        assert_eq!(failures[&info_hash], 2);
+        // Cancelled fetches don't count either way.
+        assert_eq!(
+            after_fetch(&mut failures, info_hash, Cached::Missing, &policy),
+            AfterFetch::Nothing
+        );
+        assert_eq!(failures[&info_hash], 2);
+        // Fetching it starts the count over.
+        assert_eq!(
+            after_fetch(&mut failures, info_hash, Cached::Ok, &policy),
+            AfterFetch::Fetched
+        );
+        assert!(failures.is_empty());
+        let next: Vec<_> = (0..6).map(|_| failed(&mut failures)).collect();
+        assert_eq!(
+            next,
+            [
+                AfterFetch::Retry(policy.base_delay),
+                AfterFetch::Retry(policy.base_delay * 2),
+                AfterFetch::Retry(policy.base_delay * 4),
+                AfterFetch::Nothing,
+                AfterFetch::Nothing,
+                AfterFetch::Nothing
+            ]
+        );
+        assert_eq!(failures[&info_hash], 6);
+    }
+
+    #[test]
+    fn test_needs_revalidation() {
+        let now = Utc::now();
+        let scraped = |minutes_ago| Info {
+            scrape_time: Timestamp::new(now - Duration::minutes(minutes_ago)),
+            ..Default::default()
+        };
+        assert!(!needs_revalidation(&scraped(5), None, now));
+        assert!(needs_revalidation(&scraped(120), None, now));
+        // Never scraped.
+        assert!(needs_revalidation(&Info::default(), None, now));
+        assert!(!needs_revalidation(
+            &scraped(120),
+            Some(now - Duration::minutes(1)),
+            now
+        ));
+        assert!(needs_revalidation(
+            &scraped(120),
+            Some(now - Duration::minutes(30)),
+            now
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+use super::*;
+use ::leptos::html::{Input, Textarea};
+use info_files_store::InfoFilesCacheSettings;
+use web_sys::SubmitEvent;
+
+#[component]
+pub(super) fn IndexerSettings(
+    cx: Scope,
+    indexer: RwSignal<Indexer>,
+    trackers: RwSignal<TrackerList>,
+) -> impl IntoView {
+    let input_element: NodeRef<Input> = create_node_ref(cx);
+    let endpoint = move || indexer.with(|indexer| indexer.endpoint.to_string());
+    let set_endpoint = move |endpoint: IndexerEndpoint| {
+        info!("setting indexer endpoint to {}", endpoint);
+        endpoint.save();
+        indexer.update(|indexer| indexer.endpoint = endpoint);
+    };
+    let on_preset = move |ev| {
+        let value = event_target_value(&ev);
+        // The custom option doesn't change anything until the URL is submitted.
+        if value.is_empty() {
+            return;
+        }
+        set_endpoint(IndexerEndpoint::new(value));
+    };
+    let on_submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let value = input_element().expect("<input> to exist").value();
+        if value.trim().is_empty() {
+            return;
+        }
+        set_endpoint(IndexerEndpoint::new(value.trim()));
+    };
+    let on_reset = move |_| {
+        IndexerEndpoint::clear_saved();
+        indexer.update(|indexer| indexer.endpoint = IndexerEndpoint::build_default());
+    };
+    let on_compression = move |ev| {
+        let Some(compression) = Compression::parse(&event_target_value(&ev)) else {
+            return;
+        };
+        compression.save();
+        indexer.update(|indexer| indexer.compression = compression);
+    };
+    let trackers_element: NodeRef<Textarea> = create_node_ref(cx);
+    let on_save_trackers = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let value = trackers_element().expect("<textarea> to exist").value();
+        let list = TrackerList::parse(&value);
+        list.save();
+        trackers.set(list);
+    };
+    let on_reset_trackers = move |_| {
+        TrackerList::clear_saved();
+        trackers.set(Default::default());
+    };
+    let compression_options = move || {
+        let current = indexer.with(|indexer| indexer.compression);
+        Compression::ALL
+            .into_iter()
+            .map(|compression| {
+                view! { cx,
+                    <option value=compression.as_str() selected=current == compression>
+                        {compression.as_str()}
+                    </option>
+                }
+            })
+            .collect_view(cx)
+    };
+    let preset_options = move || {
+        let current = indexer.with(|indexer| indexer.endpoint.preset());
+        view! { cx,
+            <option value="" selected=current.is_none()>"Custom"</option>
+            {EndpointPreset::ALL
+                .into_iter()
+                .map(|preset| {
+                    view! { cx,
+                        <option value=preset.url() selected=current == Some(preset)>
+                            {preset.label()}
+                        </option>
+                    }
+                })
+                .collect_view(cx)}
+        }
+    };
+    view! { cx,
+        <details class="settings">
+            <summary>"Settings"</summary>
+            <form class="settings-form" on:submit=on_submit>
+                <label>
+                    "Indexer "
+                    <select on:change=on_preset>{preset_options}</select>
+                </label>
+                <input type="text" name="indexer" prop:value=endpoint node_ref=input_element/>
+                <button type="submit">"Save"</button>
+                <button type="button" on:click=on_reset>
+                    "Reset to default"
+                </button>
+            </form>
+            <label title="Needs indexer support, falls back to plain JSON otherwise">
+                "File list compression "
+                <select on:change=on_compression>{compression_options}</select>
+            </label>
+            <form class="settings-form" on:submit=on_save_trackers>
+                <label for="trackers">"Magnet link trackers, one per line"</label>
+                <textarea
+                    id="trackers"
+                    rows="4"
+                    prop:value=move || trackers.with(ToString::to_string)
+                    node_ref=trackers_element
+                ></textarea>
+                <button type="submit">"Save"</button>
+                <button type="button" on:click=on_reset_trackers>
+                    "Reset to default"
+                </button>
+            </form>
+            <InfoFilesCacheSettings/>
+        </details>
+    }
+}
@@ -0,0 +1,245 @@
+use super::*;
+use icu_collator::Collator;
+use std::cmp::{Ordering, Reverse};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+// Like ?sort=-seeders for most seeders first.
+pub(super) const SORT_QUERY_PARAM: &str = "sort";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum SortColumn {
+    Name,
+    Seeders,
+    Size,
+    Age,
+    Files,
+    FileTypes,
+}
+
+impl SortColumn {
+    const ALL: [Self; 6] = [
+        Self::Name,
+        Self::Seeders,
+        Self::Size,
+        Self::Age,
+        Self::Files,
+        Self::FileTypes,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Seeders => "seeders",
+            Self::Size => "size",
+            Self::Age => "age",
+            Self::Files => "files",
+            Self::FileTypes => "types",
+        }
+    }
+
+    // Biggest first for counts, since that's usually what's wanted.
+    fn default_descending(self) -> bool {
+        matches!(self, Self::Seeders | Self::Size | Self::Files)
+    }
+}
+
+// Without one, results are in the order the indexer returned them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) struct SortOrder {
+    pub column: SortColumn,
+    pub descending: bool,
+}
+
+impl SortOrder {
+    // For a header click: flips the current column, or starts on a new one.
+    pub(super) fn toggle(current: Option<Self>, column: SortColumn) -> Self {
+        match current {
+            Some(current) if current.column == column => Self {
+                column,
+                descending: !current.descending,
+            },
+            _ => Self {
+                column,
+                descending: column.default_descending(),
+            },
+        }
+    }
+}
+
+impl Display for SortOrder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.descending {
+            f.write_str("-")?;
+        }
+        f.write_str(self.column.as_str())
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (descending, column) = match s.strip_prefix('-') {
+            Some(column) => (true, column),
+            None => (false, s),
+        };
+        let column = SortColumn::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == column)
+            .ok_or(())?;
+        Ok(Self { column, descending })
+    }
+}
+
+// The parts that come from info files, looked up once rather than in every comparison. None
+// until they've been fetched.
+struct SortKey(Option<Rc<FileSummary>>);
+
+impl SortKey {
+    fn new(item: &InfoItem, summaries: &FileSummaries) -> Self {
+        Self(summaries.get(&item.info_hash).cloned())
+    }
+
+    fn num_files(&self) -> Option<usize> {
+        self.0.as_ref().map(|summary| summary.num_files)
+    }
+
+    fn file_types(&self) -> Option<&String> {
+        self.0.as_ref().map(|summary| &summary.file_types_key)
+    }
+}
+
+// Missing values go last whichever way it's sorted.
+fn compare_missing_last<T>(
+    left: Option<T>,
+    right: Option<T>,
+    descending: bool,
+    compare: impl Fn(&T, &T) -> Ordering,
+) -> Ordering {
+    match (left, right) {
+        (Some(left), Some(right)) if descending => compare(&right, &left),
+        (Some(left), Some(right)) => compare(&left, &right),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn compare(
+    order: SortOrder,
+    (left_key, left): (&SortKey, &InfoItem),
+    (right_key, right): (&SortKey, &InfoItem),
+    collator: &Collator,
+) -> Ordering {
+    let by_name = |left: &String, right: &String| collator.compare(left, right);
+    let descending = order.descending;
+    let primary = match order.column {
+        SortColumn::Name => {
+            compare_missing_last(Some(&left.name), Some(&right.name), descending, |l, r| {
+                by_name(l, r)
+            })
+        }
+        SortColumn::Seeders => compare_missing_last(
+            Some(left.swarm_info.seeders),
+            Some(right.swarm_info.seeders),
+            descending,
+            Ord::cmp,
+        ),
+        SortColumn::Size => {
+            compare_missing_last(Some(left.size), Some(right.size), descending, Ord::cmp)
+        }
+        // Ascending is youngest first, like the relative times shown.
+        SortColumn::Age => compare_missing_last(
+            left.age.datetime().map(Reverse),
+            right.age.datetime().map(Reverse),
+            descending,
+            Ord::cmp,
+        ),
+        SortColumn::Files => compare_missing_last(
+            left_key.num_files(),
+            right_key.num_files(),
+            descending,
+            Ord::cmp,
+        ),
+        SortColumn::FileTypes => compare_missing_last(
+            left_key.file_types(),
+            right_key.file_types(),
+            descending,
+            |l, r| by_name(l, r),
+        ),
+    };
+    // Ties always fall back to the name then the info hash, so rows don't jump around as info
+    // files arrive.
+    primary
+        .then_with(|| by_name(&left.name, &right.name))
+        .then_with(|| left.info_hash.cmp(&right.info_hash))
+}
+
+pub(super) fn sort_items(items: &mut Vec<InfoItem>, order: SortOrder, summaries: &FileSummaries) {
+    let collator = collator();
+    let mut keyed: Vec<(SortKey, InfoItem)> = items
+        .drain(..)
+        .map(|item| (SortKey::new(&item, summaries), item))
+        .collect();
+    keyed.sort_by(|(left_key, left), (right_key, right)| {
+        compare(order, (left_key, left), (right_key, right), collator)
+    });
+    items.extend(keyed.into_iter().map(|(_, item)| item));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(byte: u8, name: &str, seeders: u32) -> InfoItem {
+        InfoItem {
+            info_hash: InfoHash::V1([byte; 20]),
+            name: name.to_owned(),
+            swarm_info: SwarmInfo {
+                seeders,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn names(items: &[InfoItem]) -> Vec<&str> {
+        items.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_order_round_trip() {
+        for s in ["name", "-seeders", "types"] {
+            assert_eq!(s.parse::<SortOrder>().unwrap().to_string(), s);
+        }
+        assert!("-".parse::<SortOrder>().is_err());
+        let order = SortOrder::toggle(None, SortColumn::Size);
+        assert!(order.descending);
+        assert!(!SortOrder::toggle(Some(order), SortColumn::Size).descending);
+        assert!(!SortOrder::toggle(Some(order), SortColumn::Name).descending);
+    }
+
+    #[test]
+    fn test_sort_items() {
+        let mut items = vec![
+            item(1, "Episode 10", 5),
+            item(2, "episode 9", 5),
+            item(3, "Episode 2", 7),
+        ];
+        let mut cache = InfoFilesCache::new();
+        cache.insert(InfoHash::V1([3; 20]), Some(Ok(Default::default())));
+        let summaries = update_file_summaries(None, &cache);
+        let order = |s: &str| s.parse::<SortOrder>().unwrap();
+        sort_items(&mut items, order("name"), &summaries);
+        assert_eq!(names(&items), ["Episode 2", "episode 9", "Episode 10"]);
+        // Ties on seeders go by name.
+        sort_items(&mut items, order("-seeders"), &summaries);
+        assert_eq!(names(&items), ["Episode 2", "episode 9", "Episode 10"]);
+        // Only one has its files yet, the rest go last in either direction.
+        sort_items(&mut items, order("files"), &summaries);
+        assert_eq!(names(&items)[0], "Episode 2");
+        sort_items(&mut items, order("-files"), &summaries);
+        assert_eq!(names(&items)[0], "Episode 2");
+    }
+}
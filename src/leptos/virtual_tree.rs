@@ -0,0 +1,252 @@
+use super::*;
+use ::leptos::html::Div;
+use filerow::FileRowTree;
+use std::ops::Range;
+
+// Rows are kept to one line so the window can be worked out from the scroll position alone.
+const ROW_HEIGHT_PX: f64 = 24.0;
+// Until the container has been measured.
+const DEFAULT_VIEWPORT_PX: f64 = 800.0;
+// Rows mounted either side of what's on screen, so fast scrolling doesn't show gaps.
+const OVERSCAN_ROWS: usize = 20;
+
+#[derive(Debug, PartialEq)]
+struct FlatNode {
+    depth: usize,
+    name: String,
+    size: u64,
+    so: Option<usize>,
+    dir: bool,
+    // One past the last node under this one.
+    end: usize,
+}
+
+// A FileRowTree in pre-order, so a node's descendants are the nodes up to its end. Nodes are
+// identified by their index, which is stable for as long as the tree is.
+#[derive(Debug, PartialEq)]
+pub(super) struct FlatFileTree {
+    nodes: Vec<FlatNode>,
+    // Just the root, so the top level is showing.
+    pub initially_expanded: HashSet<usize>,
+}
+
+impl FlatFileTree {
+    // The root is shown with the torrent's name.
+    pub(super) fn new(tree: &FileRowTree, root_name: String) -> Self {
+        let mut flat = Self {
+            nodes: vec![],
+            initially_expanded: HashSet::from([0]),
+        };
+        flat.flatten(tree, FileRowTree::ROOT);
+        flat.nodes[0].name = root_name;
+        flat
+    }
+
+    fn flatten(&mut self, tree: &FileRowTree, index: usize) {
+        let node = tree.node(index);
+        let flat_index = self.nodes.len();
+        self.nodes.push(FlatNode {
+            depth: node.depth,
+            name: node.name.to_owned(),
+            size: node.size,
+            so: node.so,
+            dir: !node.children.is_empty(),
+            end: 0,
+        });
+        for &child in &node.children {
+            self.flatten(tree, child);
+        }
+        self.nodes[flat_index].end = self.nodes.len();
+    }
+
+    // Nodes whose ancestors are all expanded, in display order. Collapsed directories are
+    // skipped over whole, so this is proportional to what's shown rather than the whole tree.
+    fn visible(&self, expanded: &HashSet<usize>) -> Vec<usize> {
+        let mut visible = vec![];
+        let mut index = 0;
+        while let Some(node) = self.nodes.get(index) {
+            visible.push(index);
+            index = if node.dir && !expanded.contains(&index) {
+                node.end
+            } else {
+                index + 1
+            };
+        }
+        visible
+    }
+
+    // The file indexes at and under a node.
+    pub(super) fn file_indexes(&self, index: usize) -> Vec<usize> {
+        self.nodes[index..self.nodes[index].end]
+            .iter()
+            .filter_map(|node| node.so)
+            .collect()
+    }
+}
+
+// Positions in the visible rows to mount for the scroll position.
+fn visible_window(scroll_top: f64, viewport_height: f64, total: usize) -> Range<usize> {
+    let first = (scroll_top.max(0.0) / ROW_HEIGHT_PX) as usize;
+    let shown = (viewport_height / ROW_HEIGHT_PX).ceil() as usize + 1;
+    let start = first.saturating_sub(OVERSCAN_ROWS).min(total);
+    let end = (first + shown + OVERSCAN_ROWS).min(total);
+    start..end
+}
+
+fn spacer_height(rows: usize) -> String {
+    format!("{}px", rows as f64 * ROW_HEIGHT_PX)
+}
+
+// Only the rows in and around the viewport are in the DOM, with spacers standing in for the rest,
+// so torrents with a huge number of files stay responsive.
+#[component]
+pub(super) fn VirtualFileTree(
+    cx: Scope,
+    tree: Rc<FlatFileTree>,
+    expanded: RwSignal<HashSet<usize>>,
+) -> impl IntoView {
+    let container: NodeRef<Div> = create_node_ref(cx);
+    let scroll_top = create_rw_signal(cx, 0.0);
+    let viewport_height = create_rw_signal(cx, DEFAULT_VIEWPORT_PX);
+    let measure = move || {
+        let Some(container) = container() else {
+            return;
+        };
+        scroll_top.set(f64::from(container.scroll_top()));
+        viewport_height.set(f64::from(container.client_height()));
+    };
+    // Once it's mounted, and then whenever it scrolls.
+    create_effect(cx, move |_| measure());
+    let on_scroll = move |_| measure();
+    let visible = {
+        let tree = tree.clone();
+        create_memo(cx, move |_| {
+            expanded.with(|expanded| tree.visible(expanded))
+        })
+    };
+    let window = create_memo(cx, move |_| {
+        visible_window(scroll_top(), viewport_height(), visible.with(Vec::len))
+    });
+    let mounted = move || {
+        let window = window();
+        visible.with(|visible| visible[window].to_vec())
+    };
+    let top = move || spacer_height(window().start);
+    let bottom = move || spacer_height(visible.with(Vec::len) - window().end);
+    let row = move |cx: Scope, index: usize| view_row(cx, &tree, index, expanded, visible);
+    view! { cx,
+        <div class="torrent-files-scroll" node_ref=container on:scroll=on_scroll>
+            <table class="torrent-files">
+                <caption>"Files"</caption>
+                <tbody>
+                    <tr class="spacer" style:height=top></tr>
+                    <For each=mounted key=|index| *index view=row/>
+                    <tr class="spacer" style:height=bottom></tr>
+                </tbody>
+            </table>
+        </div>
+    }
+}
+
+fn view_row(
+    cx: Scope,
+    tree: &FlatFileTree,
+    index: usize,
+    expanded: RwSignal<HashSet<usize>>,
+    visible: Memo<Vec<usize>>,
+) -> impl IntoView {
+    let selection =
+        use_context::<RwSignal<FileSelection>>(cx).expect("file selection to be provided");
+    let node = &tree.nodes[index];
+    let file_indexes = Rc::new(tree.file_indexes(index));
+    let no_files = file_indexes.is_empty();
+    let selection_state = {
+        let file_indexes = file_indexes.clone();
+        create_memo(cx, move |_| {
+            selection.with(|selection| selection.state(&file_indexes))
+        })
+    };
+    let on_change = move |_| selection.update(|selection| selection.toggle(&file_indexes));
+    let dir = node.dir;
+    let name = if dir {
+        let on_click = move |ev: web_sys::MouseEvent| {
+            // Without this the router would treat "#" as a navigation.
+            ev.prevent_default();
+            expanded.update(|expanded| {
+                if !expanded.remove(&index) {
+                    expanded.insert(index);
+                }
+            })
+        };
+        view! { cx, <a href="#" on:click=on_click>{node.name.clone()}</a> }.into_view(cx)
+    } else {
+        node.name.clone().into_view(cx)
+    };
+    // By where the row is shown rather than where it is in the DOM, which has only the window.
+    let stripe = move || {
+        visible.with(|visible| {
+            visible
+                .binary_search(&index)
+                .map_or(false, |row| row % 2 == 1)
+        })
+    };
+    view! { cx,
+        <tr class:stripe=stripe>
+            <td style:padding-left=format!("{}em", node.depth)>
+                <input
+                    type="checkbox"
+                    disabled=no_files
+                    prop:checked=move || selection_state() == SelectionState::All
+                    prop:indeterminate=move || selection_state() == SelectionState::Partial
+                    on:change=on_change
+                />
+                <i
+                    style:width="1em"
+                    style:padding-right="0.5em"
+                    class="fa-regular"
+                    class:fa-file=!dir
+                    class:fa-folder=dir
+                ></i>
+                {name}
+            </td>
+            <td>{format_size(node.size)}</td>
+        </tr>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filerow::info_files_to_file_rows;
+
+    #[test]
+    fn test_visible() {
+        let file_rows =
+            info_files_to_file_rows(&[(&["a", "1"][..], 1), (&["a", "2"], 1), (&["b"], 1)].map(
+                |(path, length)| UpvertedFile {
+                    path: path.iter().map(|&part| part.into()).collect(),
+                    length,
+                },
+            ));
+        let tree = FlatFileTree::new(&FileRowTree::new(&file_rows), "root".to_owned());
+        assert_eq!(tree.nodes[0].name, "root");
+        assert_eq!(tree.nodes[0].size, 3);
+        let mut expanded = tree.initially_expanded.clone();
+        assert_eq!(expanded, HashSet::from([0]));
+        // "a" is collapsed, so its files are skipped.
+        assert_eq!(tree.visible(&expanded), [0, 1, 4]);
+        expanded.insert(1);
+        assert_eq!(tree.visible(&expanded), [0, 1, 2, 3, 4]);
+        assert_eq!(tree.file_indexes(1), [0, 1]);
+        assert_eq!(tree.file_indexes(0), [0, 1, 2]);
+        assert_eq!(tree.visible(&HashSet::new()), [0]);
+    }
+
+    #[test]
+    fn test_visible_window() {
+        assert_eq!(visible_window(0.0, 240.0, 100_000), 0..31);
+        assert_eq!(visible_window(24_000.0, 240.0, 100_000), 980..1031);
+        assert_eq!(visible_window(24_000.0, 240.0, 1005), 980..1005);
+        assert_eq!(visible_window(0.0, 240.0, 5), 0..5);
+    }
+}
@@ -1,5 +1,3 @@
-#![feature(iter_order_by)]
-
 use icu_collator::Numeric::On;
 use icu_collator::{Collator, CollatorOptions};
 use log::{debug, info};
@@ -8,29 +6,102 @@ use std::ops::Deref;
 use std::sync::Arc;
 
 pub mod api;
-mod filerow;
+#[cfg(any(test, feature = "fake-indexer"))]
+pub mod fake_indexer;
+pub mod filerow;
 #[cfg(feature = "leptos")]
 mod leptos;
+pub mod magnet;
+pub mod metainfo;
+pub mod query;
 #[cfg(feature = "yew")]
 mod yew;
 
 pub use self::leptos::*;
 pub use crate::leptos::mount_to_body;
 
-fn make_magnet_link(info_hash: &str) -> String {
-    "magnet:?xt=urn:btih:".to_owned() + info_hash
-}
-
 pub type Result<T> = std::result::Result<T, CloneableError>;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error(transparent)]
-    GlooNet(#[from] gloo_net::Error),
+    #[error("network error fetching {url}: {message}")]
+    Network { url: String, message: String },
+    // Browsers don't tell us why a fetch failed, so these are guesses from the URLs involved.
+    #[error("fetching {url} was blocked as mixed content")]
+    MixedContent { url: String },
+    #[error("fetching {url} failed, possibly due to CORS: {message}")]
+    CorsSuspected { url: String, message: String },
+    #[error("indexer responded with HTTP {status}: {body}")]
+    HttpStatus { status: u16, body: String },
+    // The Err field in Go responses.
+    #[error("indexer error: {0}")]
+    Indexer(String),
+    #[error("decoding indexer response at {path}: {source}")]
+    Decode {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("decompressing {encoding} response: {message}")]
+    Decompress {
+        encoding: &'static str,
+        message: String,
+    },
+    #[error("invalid info hash {0:?}")]
+    InvalidInfoHash(String),
+    #[error("invalid magnet link: {0}")]
+    InvalidMagnet(String),
+    #[error("invalid torrent file: {0}")]
+    InvalidTorrentFile(String),
+    #[error("invalid search: {0}")]
+    InvalidQuery(String),
+    #[error("request cancelled")]
+    Cancelled,
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
 }
 
+impl Error {
+    // Worth trying the same request again.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            // A fetch failing with CORS-ish symptoms is also what a restarting server looks like.
+            Self::Network { .. } | Self::CorsSuspected { .. } => true,
+            Self::HttpStatus { status, .. } => is_transient_status(*status),
+            _ => false,
+        }
+    }
+
+    // Something to tell the user to do about it, if there's anything.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Network { .. } => Some("Check the indexer is running and reachable."),
+            Self::MixedContent { .. } => Some(
+                "This page is served over https but the indexer isn't. Use an https indexer \
+                 endpoint in Settings, or load this page over http.",
+            ),
+            Self::CorsSuspected { .. } => Some(
+                "Check the indexer is running and allows requests from this origin, or pick a \
+                 different endpoint in Settings.",
+            ),
+            Self::HttpStatus { status: 404, .. } => {
+                Some("The indexer endpoint may be wrong, check Settings.")
+            }
+            Self::HttpStatus { status, .. } if *status >= 500 => {
+                Some("The indexer had a problem, try again later.")
+            }
+            Self::Decode { .. } => {
+                Some("The indexer sent something unexpected, it may be an incompatible version.")
+            }
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn is_transient_status(status: u16) -> bool {
+    matches!(status, 429 | 502 | 503 | 504)
+}
+
 #[derive(Clone, Debug)]
 pub struct CloneableError(Arc<Error>);
 
@@ -65,7 +136,16 @@ impl Display for CloneableError {
 
 impl std::error::Error for CloneableError {}
 
-pub(crate) fn new_collator() -> Collator {
+thread_local! {
+    // Loading the collation data isn't free, so there's one per thread for the whole program.
+    static COLLATOR: &'static Collator = Box::leak(Box::new(new_collator()));
+}
+
+pub(crate) fn collator() -> &'static Collator {
+    COLLATOR.with(|collator| *collator)
+}
+
+fn new_collator() -> Collator {
     let mut options = CollatorOptions::new();
     options.numeric = Some(On);
     icu_collator::Collator::try_new_unstable(
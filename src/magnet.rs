@@ -0,0 +1,295 @@
+use crate::api::{local_storage, InfoHash};
+use crate::Error;
+use log::warn;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+const LOCAL_STORAGE_KEY: &str = "dhtsearch.trackers";
+const SCHEME: &str = "magnet:?";
+
+// Without any trackers clients can only find peers through the DHT, which can take a while.
+const DEFAULT_TRACKERS: [&str; 2] = [
+    "udp://tracker.opentrackr.org:1337/announce",
+    "udp://open.demonii.com:1337/announce",
+];
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Magnet {
+    // xt. Hybrid torrents have a v1 and a v2 one.
+    pub info_hashes: Vec<InfoHash>,
+    // dn
+    pub display_name: Option<String>,
+    // xl
+    pub exact_length: Option<u64>,
+    // tr
+    pub trackers: Vec<String>,
+    // so, from BEP 53. File indexes, in ranges as they appear in the URI.
+    pub select_only: Vec<RangeInclusive<usize>>,
+    // Parameters we don't interpret, like ws and x.pe, kept so they survive a round trip.
+    pub extra: Vec<(String, String)>,
+}
+
+impl Magnet {
+    pub fn new(info_hashes: impl IntoIterator<Item = InfoHash>) -> Self {
+        Self {
+            info_hashes: info_hashes.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_display_name(mut self, display_name: impl Into<String>) -> Self {
+        self.display_name = Some(display_name.into());
+        self
+    }
+
+    pub fn with_exact_length(mut self, exact_length: u64) -> Self {
+        self.exact_length = Some(exact_length);
+        self
+    }
+
+    pub fn with_trackers(mut self, trackers: impl IntoIterator<Item = String>) -> Self {
+        self.trackers.extend(trackers);
+        self
+    }
+
+    // Collapses the file indexes into ranges.
+    pub fn with_select_only(mut self, files: impl IntoIterator<Item = usize>) -> Self {
+        let mut files: Vec<usize> = files.into_iter().collect();
+        files.sort_unstable();
+        files.dedup();
+        let mut ranges: Vec<RangeInclusive<usize>> = vec![];
+        for file in files {
+            match ranges.last_mut() {
+                Some(last) if *last.end() + 1 == file => *last = *last.start()..=file,
+                _ => ranges.push(file..=file),
+            }
+        }
+        self.select_only = ranges;
+        self
+    }
+
+    pub fn selected_files(&self) -> impl Iterator<Item = usize> + '_ {
+        self.select_only.iter().flat_map(Clone::clone)
+    }
+}
+
+impl Display for Magnet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let encode = |value: &str| -> String {
+            url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+        };
+        // Info hashes and so are left unescaped, clients are more likely to cope with that than
+        // with escaped colons and commas.
+        let mut params: Vec<String> = self
+            .info_hashes
+            .iter()
+            .map(|info_hash| format!("xt={}", info_hash.magnet_urn()))
+            .collect();
+        if let Some(display_name) = &self.display_name {
+            params.push(format!("dn={}", encode(display_name)));
+        }
+        if let Some(exact_length) = self.exact_length {
+            params.push(format!("xl={}", exact_length));
+        }
+        for tracker in &self.trackers {
+            params.push(format!("tr={}", encode(tracker)));
+        }
+        if !self.select_only.is_empty() {
+            let ranges: Vec<String> = self
+                .select_only
+                .iter()
+                .map(|range| {
+                    if range.start() == range.end() {
+                        range.start().to_string()
+                    } else {
+                        format!("{}-{}", range.start(), range.end())
+                    }
+                })
+                .collect();
+            params.push(format!("so={}", ranges.join(",")));
+        }
+        for (key, value) in &self.extra {
+            params.push(format!("{}={}", encode(key), encode(value)));
+        }
+        write!(f, "{}{}", SCHEME, params.join("&"))
+    }
+}
+
+impl FromStr for Magnet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |message: &str| Error::InvalidMagnet(format!("{}: {}", message, s));
+        let query = s
+            .get(..SCHEME.len())
+            .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+            .map(|_| &s[SCHEME.len()..])
+            .ok_or_else(|| invalid("not a magnet link"))?;
+        let mut magnet = Self::default();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            // Multiple values can be numbered, like xt.1 and xt.2.
+            let base_key = key.split_once('.').map_or(&*key, |(base, _)| base);
+            match base_key {
+                "xt" => match value
+                    .strip_prefix("urn:btih:")
+                    .or_else(|| value.strip_prefix("urn:btmh:"))
+                {
+                    Some(info_hash) => magnet.info_hashes.push(info_hash.parse()?),
+                    None => magnet.extra.push((key.into_owned(), value.into_owned())),
+                },
+                "dn" => magnet.display_name = Some(value.into_owned()),
+                "xl" => magnet.exact_length = Some(value.parse().map_err(|_| invalid("bad xl"))?),
+                "tr" => magnet.trackers.push(value.into_owned()),
+                "so" => {
+                    magnet.select_only =
+                        parse_select_only(&value).ok_or_else(|| invalid("bad so"))?
+                }
+                _ => magnet.extra.push((key.into_owned(), value.into_owned())),
+            }
+        }
+        if magnet.info_hashes.is_empty() {
+            return Err(invalid("no info hash"));
+        }
+        Ok(magnet)
+    }
+}
+
+fn parse_select_only(value: &str) -> Option<Vec<RangeInclusive<usize>>> {
+    value
+        .split(',')
+        .map(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+            (start <= end).then_some(start..=end)
+        })
+        .collect()
+}
+
+// Trackers added to the magnet links we make, one per line in Settings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackerList(pub Vec<String>);
+
+impl TrackerList {
+    pub fn parse(text: &str) -> Self {
+        Self(
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(ToOwned::to_owned)
+                .collect(),
+        )
+    }
+
+    pub fn load() -> Self {
+        local_storage()
+            .and_then(|storage| storage.get_item(LOCAL_STORAGE_KEY).ok().flatten())
+            .map(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        if let Err(err) = storage.set_item(LOCAL_STORAGE_KEY, &self.to_string()) {
+            warn!("saving tracker list: {:?}", err);
+        }
+    }
+
+    pub fn clear_saved() {
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        if let Err(err) = storage.remove_item(LOCAL_STORAGE_KEY) {
+            warn!("clearing saved tracker list: {:?}", err);
+        }
+    }
+}
+
+impl Default for TrackerList {
+    fn default() -> Self {
+        Self(DEFAULT_TRACKERS.map(ToOwned::to_owned).to_vec())
+    }
+}
+
+impl Display for TrackerList {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AARON_SWARTZ: &str = "40f3761b9080949ca6ffed3522ad872bc0bef41b";
+    const V2: &str = "1220caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e";
+
+    #[test]
+    fn test_build() {
+        let magnet = Magnet::new([AARON_SWARTZ.parse().unwrap()])
+            .with_display_name("The Internet's Own Boy.mp4")
+            .with_exact_length(1701564468)
+            .with_trackers(["udp://tracker.example:1337/announce".to_owned()])
+            .with_select_only([0, 4, 2, 3, 7]);
+        assert_eq!(
+            magnet.to_string(),
+            format!(
+                "magnet:?xt=urn:btih:{}&dn=The+Internet%27s+Own+Boy.mp4&xl=1701564468\
+                 &tr=udp%3A%2F%2Ftracker.example%3A1337%2Fannounce&so=0,2-4,7",
+                AARON_SWARTZ
+            )
+        );
+        assert_eq!(magnet.selected_files().collect::<Vec<_>>(), [0, 2, 3, 4, 7]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let magnet = Magnet {
+            info_hashes: vec![AARON_SWARTZ.parse().unwrap(), V2.parse().unwrap()],
+            display_name: Some("a & b = c/d".to_owned()),
+            exact_length: Some(3),
+            trackers: vec![
+                "http://tracker.example/announce?passkey=1&x=2".to_owned(),
+                "udp://other.example:80".to_owned(),
+            ],
+            select_only: vec![1..=1, 3..=9],
+            extra: vec![("ws".to_owned(), "https://seed.example/files/".to_owned())],
+        };
+        assert_eq!(magnet.to_string().parse::<Magnet>().unwrap(), magnet);
+    }
+
+    #[test]
+    fn test_parse_foreign() {
+        // Upper case base32, numbered topics and %20 rather than + for spaces.
+        let magnet: Magnet = "MAGNET:?xt.1=urn:btih:IDZXMG4QQCKJZJX75U2SFLMHFPAL55A3\
+             &dn=Aaron%20Swartz&x.pe=1.2.3.4:5&tr.1=udp%3A%2F%2Ft.example%3A1"
+            .parse()
+            .unwrap();
+        assert_eq!(magnet.info_hashes, [AARON_SWARTZ.parse().unwrap()]);
+        assert_eq!(magnet.display_name.as_deref(), Some("Aaron Swartz"));
+        assert_eq!(magnet.trackers, ["udp://t.example:1"]);
+        assert_eq!(magnet.extra, [("x.pe".to_owned(), "1.2.3.4:5".to_owned())]);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for s in [
+            "http://example.com/",
+            "magnet:?dn=nothing",
+            "magnet:?xt=urn:btih:nope",
+            &format!("magnet:?xt=urn:btih:{}&so=3-1", AARON_SWARTZ),
+            &format!("magnet:?xt=urn:btih:{}&xl=big", AARON_SWARTZ),
+        ] {
+            assert!(s.parse::<Magnet>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_tracker_list() {
+        let trackers = TrackerList::parse("  udp://a:1\n\nhttp://b/announce \n");
+        assert_eq!(trackers.0, ["udp://a:1", "http://b/announce"]);
+        assert_eq!(TrackerList::parse(&trackers.to_string()), trackers);
+    }
+}
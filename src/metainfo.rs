@@ -0,0 +1,256 @@
+use crate::api::{
+    DecodedBytes, File, FileLength, FileTree, FileTreeFile, FileTreeNode, Info, InfoFiles,
+    InfoHash, Timestamp,
+};
+use crate::Error;
+use bencode::Value;
+use chrono::{TimeZone, Utc};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+
+mod bencode;
+
+// A .torrent file opened locally, read into the same shape the indexer sends so the rest of the UI
+// doesn't care where it came from.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Metainfo {
+    pub info_files: InfoFiles,
+    // announce and announce-list, in order without duplicates.
+    pub trackers: Vec<String>,
+}
+
+impl Metainfo {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let torrent = bencode::decode(data)?;
+        let info = torrent
+            .get("info")
+            .filter(|info| info.as_dict().is_some())
+            .ok_or_else(|| invalid("no info dict"))?;
+        let raw_info = info.raw().unwrap();
+        let has_v1 = info.get("pieces").is_some();
+        let has_v2 = info.get("meta version").and_then(Value::as_int) == Some(2);
+        let info_hash_v1 = InfoHash::V1(Sha1::digest(raw_info).into());
+        let info_hash_v2 = InfoHash::V2(Sha256::digest(raw_info).into());
+        let (info_hash, info_hash_v2) = match (has_v1, has_v2) {
+            (true, true) => (info_hash_v1, Some(info_hash_v2)),
+            (true, false) => (info_hash_v1, None),
+            (false, true) => (info_hash_v2, None),
+            (false, false) => return Err(invalid("neither v1 pieces nor v2 meta version")),
+        };
+        let name = info
+            .get("name.utf-8")
+            .or_else(|| info.get("name"))
+            .and_then(Value::as_bytes)
+            .ok_or_else(|| invalid("no name"))?;
+        let files = if has_v1 { v1_files(info)? } else { vec![] };
+        let file_tree = if has_v2 {
+            let file_tree = info
+                .get("file tree")
+                .ok_or_else(|| invalid("no file tree"))?;
+            Some(FileTree(file_tree_dir(file_tree)?))
+        } else {
+            None
+        };
+        // The closest thing a .torrent has to when the indexer first saw it.
+        let age = torrent
+            .get("creation date")
+            .and_then(Value::as_int)
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+            .map_or_else(Timestamp::zero, Timestamp::new);
+        let mut trackers: Vec<String> = vec![];
+        let announce_list = torrent
+            .get("announce-list")
+            .and_then(Value::as_list)
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|tier| tier.as_list().unwrap_or_default());
+        for tracker in torrent.get("announce").into_iter().chain(announce_list) {
+            let Some(tracker) = tracker.as_bytes() else {
+                continue;
+            };
+            let tracker = String::from_utf8_lossy(tracker).into_owned();
+            if !tracker.is_empty() && !trackers.contains(&tracker) {
+                trackers.push(tracker);
+            }
+        }
+        Ok(Self {
+            info_files: InfoFiles::new(
+                Info {
+                    name: DecodedBytes::from_bytes(name.to_vec()).into(),
+                    info_hash,
+                    info_hash_v2,
+                    age,
+                    ..Default::default()
+                },
+                files,
+                file_tree,
+            ),
+            trackers,
+        })
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidTorrentFile(message.to_owned())
+}
+
+fn length(value: &Value) -> Result<FileLength, Error> {
+    value
+        .get("length")
+        .and_then(Value::as_int)
+        .filter(|length| *length >= 0)
+        .ok_or_else(|| invalid("bad file length"))
+}
+
+fn v1_files(info: &Value) -> Result<Vec<File>, Error> {
+    let Some(files) = info.get("files") else {
+        return Ok(vec![File {
+            path: None,
+            length: length(info)?,
+        }]);
+    };
+    files
+        .as_list()
+        .ok_or_else(|| invalid("files isn't a list"))?
+        .iter()
+        .map(|file| {
+            let parts = file
+                .get("path.utf-8")
+                .or_else(|| file.get("path"))
+                .and_then(Value::as_list)
+                .ok_or_else(|| invalid("file without a path"))?
+                .iter()
+                .map(|part| {
+                    part.as_bytes()
+                        .map(<[u8]>::to_vec)
+                        .ok_or_else(|| invalid("bad path component"))
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(File {
+                path: Some(DecodedBytes::from_parts(parts)),
+                length: length(file)?,
+            })
+        })
+        .collect()
+}
+
+fn file_tree_dir(dir: &Value) -> Result<BTreeMap<Vec<u8>, FileTreeNode>, Error> {
+    dir.as_dict()
+        .ok_or_else(|| invalid("file tree entry isn't a dict"))?
+        .iter()
+        .map(|(name, node)| {
+            let node = match node.get("") {
+                Some(file) => FileTreeNode::File {
+                    file: FileTreeFile {
+                        length: length(file)?,
+                        pieces_root: file
+                            .get("pieces root")
+                            .and_then(Value::as_bytes)
+                            .map(to_hex),
+                    },
+                },
+                None => FileTreeNode::Dir(file_tree_dir(node)?),
+            };
+            Ok((name.to_vec(), node))
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1_MULTI_FILE: &[u8] =
+        b"d8:announce25:http://t.example/announce13:creation datei1700000000e\
+        4:infod5:filesld6:lengthi3e4:pathl1:a5:b.txteed6:lengthi4e4:pathl5:c.mkveee\
+        4:name4:test12:piece lengthi16384e6:pieces20:xxxxxxxxxxxxxxxxxxxxee";
+
+    fn paths(info_files: &InfoFiles) -> Vec<(Vec<String>, FileLength)> {
+        info_files
+            .files()
+            .iter()
+            .map(|file| {
+                let path = file
+                    .path
+                    .iter()
+                    .flatten()
+                    .map(ToString::to_string)
+                    .collect();
+                (path, file.length)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_v1() {
+        let metainfo = Metainfo::from_bytes(V1_MULTI_FILE).unwrap();
+        let info = &metainfo.info_files.info;
+        assert_eq!(
+            info.info_hash.to_string(),
+            "e49b30406872d35ade0958279ea592b4169b799d"
+        );
+        assert_eq!(info.info_hash_v2, None);
+        assert_eq!(info.name.as_str(), "test");
+        assert_eq!(info.age.datetime().unwrap().timestamp(), 1700000000);
+        assert_eq!(metainfo.trackers, ["http://t.example/announce"]);
+        assert_eq!(
+            paths(&metainfo.info_files),
+            [
+                (vec!["a".to_owned(), "b.txt".to_owned()], 3),
+                (vec!["c.mkv".to_owned()], 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_v2() {
+        let mut data = b"d4:infod9:file treed1:ad5:b.txtd0:d6:lengthi3e11:pieces root32:".to_vec();
+        data.extend([1; 32]);
+        data.extend(
+            b"eee5:c.mkvd0:d6:lengthi4eeee12:meta versioni2e4:name4:test12:piece lengthi16384eee",
+        );
+        let metainfo = Metainfo::from_bytes(&data).unwrap();
+        let info = &metainfo.info_files.info;
+        assert_eq!(
+            info.info_hash.to_string(),
+            "12209c4836b592ae73663a892a58e61bfc4a0f712cbf4eb1996f815b10870ac727b5"
+        );
+        assert!(info.age.is_zero());
+        // There's no v1 list, so it comes from the file tree.
+        assert_eq!(metainfo.info_files.files.len(), 2);
+        assert_eq!(
+            paths(&metainfo.info_files),
+            [
+                (vec!["a".to_owned(), "b.txt".to_owned()], 3),
+                (vec!["c.mkv".to_owned()], 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_file() {
+        let metainfo =
+            Metainfo::from_bytes(b"d4:infod6:lengthi5e4:name5:a.iso6:pieces0:ee").unwrap();
+        assert_eq!(
+            metainfo.info_files.upverted_files()[0].path,
+            [DecodedBytes::from("a.iso")]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        for data in [
+            &b"de"[..],
+            b"d4:infoi1ee",
+            b"d4:infod4:name1:aee",
+            b"d4:infod6:pieces0:4:name1:aee",
+        ] {
+            assert!(Metainfo::from_bytes(data).is_err(), "{:?}", data);
+        }
+    }
+}
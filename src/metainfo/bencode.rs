@@ -0,0 +1,202 @@
+use crate::Error;
+use std::collections::BTreeMap;
+
+// Deeper than any real file tree, shallow enough not to blow the stack on a hostile file.
+const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, PartialEq, Debug)]
+pub(super) enum Value<'a> {
+    Int(i64),
+    Bytes(&'a [u8]),
+    List(Vec<Value<'a>>),
+    // raw is the dict exactly as it was encoded, which is what info hashes are taken over.
+    Dict {
+        entries: BTreeMap<&'a [u8], Value<'a>>,
+        raw: &'a [u8],
+    },
+}
+
+impl<'a> Value<'a> {
+    pub(super) fn get(&self, key: &str) -> Option<&Value<'a>> {
+        self.as_dict()?.get(key.as_bytes())
+    }
+
+    pub(super) fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(int) => Some(*int),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Self::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_list(&self) -> Option<&[Value<'a>]> {
+        match self {
+            Self::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_dict(&self) -> Option<&BTreeMap<&'a [u8], Value<'a>>> {
+        match self {
+            Self::Dict { entries, .. } => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub(super) fn raw(&self) -> Option<&'a [u8]> {
+        match self {
+            Self::Dict { raw, .. } => Some(raw),
+            _ => None,
+        }
+    }
+}
+
+pub(super) fn decode(data: &[u8]) -> Result<Value<'_>, Error> {
+    let mut decoder = Decoder { data, pos: 0 };
+    let value = decoder.value(0)?;
+    if decoder.pos != data.len() {
+        return Err(decoder.error("trailing data"));
+    }
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidTorrentFile(format!("{} at byte {}", message, self.pos))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value<'a>, Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        let start = self.pos;
+        match self.peek() {
+            Some(b'i') => {
+                self.pos += 1;
+                let digits = self.until(b'e')?;
+                std::str::from_utf8(digits)
+                    .ok()
+                    .and_then(|digits| digits.parse().ok())
+                    .map(Value::Int)
+                    .ok_or_else(|| self.error("bad integer"))
+            }
+            Some(b'l') => {
+                self.pos += 1;
+                let mut list = vec![];
+                while !self.end_of_collection()? {
+                    list.push(self.value(depth + 1)?);
+                }
+                Ok(Value::List(list))
+            }
+            Some(b'd') => {
+                self.pos += 1;
+                let mut entries = BTreeMap::new();
+                while !self.end_of_collection()? {
+                    let key = self.bytes()?;
+                    let value = self.value(depth + 1)?;
+                    entries.insert(key, value);
+                }
+                Ok(Value::Dict {
+                    entries,
+                    raw: &self.data[start..self.pos],
+                })
+            }
+            Some(b'0'..=b'9') => self.bytes().map(Value::Bytes),
+            Some(_) => Err(self.error("unexpected byte")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    // Consumes the 'e' if it's there.
+    fn end_of_collection(&mut self) -> Result<bool, Error> {
+        match self.peek() {
+            Some(b'e') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    // Everything up to the delimiter, which is consumed.
+    fn until(&mut self, delimiter: u8) -> Result<&'a [u8], Error> {
+        let data = self.data;
+        let len = data[self.pos..]
+            .iter()
+            .position(|&byte| byte == delimiter)
+            .ok_or_else(|| self.error("unexpected end"))?;
+        let bytes = &data[self.pos..self.pos + len];
+        self.pos += len + 1;
+        Ok(bytes)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.until(b':')?;
+        let len: usize = std::str::from_utf8(len)
+            .ok()
+            .and_then(|len| len.parse().ok())
+            .ok_or_else(|| self.error("bad string length"))?;
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| self.error("string runs past the end"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let data = b"d3:agei-3e4:listl1:ai1ee4:subdd1:x0:ee";
+        let value = decode(data).unwrap();
+        assert_eq!(value.get("age").and_then(Value::as_int), Some(-3));
+        assert_eq!(
+            value.get("list").and_then(Value::as_list),
+            Some(&[Value::Bytes(b"a"), Value::Int(1)][..])
+        );
+        assert_eq!(
+            value.get("subd").and_then(Value::raw),
+            Some(&b"d1:x0:e"[..])
+        );
+        assert_eq!(value.raw(), Some(&data[..]));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        for data in [
+            &b""[..],
+            b"i12",
+            b"ixe",
+            b"5:abc",
+            b"l1:a",
+            b"d1:ae",
+            b"i1ei2e",
+            b"x",
+            &[b'l'; MAX_DEPTH + 2],
+        ] {
+            assert!(decode(data).is_err(), "{:?}", data);
+        }
+    }
+}
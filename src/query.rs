@@ -0,0 +1,424 @@
+use crate::api::{InfoFiles, InfoItem};
+use crate::Error;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+
+// What's typed into the search box. The indexer only takes a text query (s=), so words and
+// phrases go there and the field filters are applied to the results as they come in.
+//
+//     big buck bunny "open movie" -trailer size:>1GB seeders:>=10 age:<30d ext:mkv,mp4 files:<5
+//
+// Filters can be negated with a leading '-' too. Unknown fields are just words, so things like
+// "re:zero" still search as typed.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SearchQuery {
+    pub terms: Vec<Term>,
+    pub filters: Vec<Filter>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Term {
+    pub text: String,
+    pub phrase: bool,
+    pub excluded: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Filter {
+    pub field: Field,
+    pub negated: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Field {
+    Size(Comparison<u64>),
+    Seeders(Comparison<u32>),
+    Age(Comparison<Duration>),
+    // Any of these, lower case.
+    Ext(Vec<String>),
+    Files(Comparison<usize>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Comparison<T> {
+    pub op: Op,
+    pub value: T,
+}
+
+impl<T: PartialOrd> Comparison<T> {
+    pub fn matches(&self, actual: T) -> bool {
+        match self.op {
+            Op::Lt => actual < self.value,
+            Op::Le => actual <= self.value,
+            Op::Eq => actual == self.value,
+            Op::Ge => actual >= self.value,
+            Op::Gt => actual > self.value,
+        }
+    }
+}
+
+impl SearchQuery {
+    // Sent as s=. Excluded terms aren't, the indexer has no way to say "not".
+    pub fn server_query(&self) -> String {
+        let included: Vec<String> = self
+            .terms
+            .iter()
+            .filter(|term| !term.excluded)
+            .map(|term| {
+                if term.phrase {
+                    format!("\"{}\"", term.text)
+                } else {
+                    term.text.clone()
+                }
+            })
+            .collect();
+        included.join(" ")
+    }
+
+    // None when it depends on info files that haven't arrived yet.
+    pub fn matches(
+        &self,
+        item: &InfoItem,
+        files: Option<FileFacts>,
+        now: DateTime<Utc>,
+    ) -> Option<bool> {
+        let name = item.name.to_lowercase();
+        let excluded = self
+            .terms
+            .iter()
+            .filter(|term| term.excluded)
+            .any(|term| name.contains(&term.text.to_lowercase()));
+        if excluded {
+            return Some(false);
+        }
+        let mut undecided = false;
+        for filter in &self.filters {
+            let matched = match &filter.field {
+                Field::Size(comparison) => Some(comparison.matches(item.size)),
+                Field::Seeders(comparison) => Some(comparison.matches(item.swarm_info.seeders)),
+                // Torrents without an age can't be placed, so they never match.
+                Field::Age(comparison) => {
+                    Some(item.age.age(now).is_some_and(|age| comparison.matches(age)))
+                }
+                Field::Ext(extensions) => files.map(|files| {
+                    extensions
+                        .iter()
+                        .any(|want| files.extensions.contains(want))
+                }),
+                Field::Files(comparison) => files.map(|files| comparison.matches(files.num_files)),
+            };
+            match matched {
+                Some(matched) if matched == filter.negated => return Some(false),
+                Some(_) => {}
+                None => undecided = true,
+            }
+        }
+        if undecided {
+            None
+        } else {
+            Some(true)
+        }
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut query = Self::default();
+        for token in tokenize(s)? {
+            let (excluded, text) = match token.text.strip_prefix('-') {
+                Some(rest) => (true, rest),
+                None => (false, &*token.text),
+            };
+            if token.quoted {
+                let text = text.trim_matches('"');
+                if !text.is_empty() {
+                    query.terms.push(Term {
+                        text: text.to_owned(),
+                        phrase: true,
+                        excluded,
+                    });
+                }
+                continue;
+            }
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(field) = parse_field(text)? {
+                query.filters.push(Filter {
+                    field,
+                    negated: excluded,
+                });
+                continue;
+            }
+            query.terms.push(Term {
+                text: text.to_owned(),
+                phrase: false,
+                excluded,
+            });
+        }
+        // The indexer needs something to search for, filters only narrow that down.
+        if query.server_query().is_empty() && query != Self::default() {
+            return Err(invalid("add a word or phrase to search for"));
+        }
+        Ok(query)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidQuery(message.into())
+}
+
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+// Splits on whitespace, except inside double quotes.
+fn tokenize(s: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    for c in s.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(Token {
+                        text: std::mem::take(&mut current),
+                        quoted,
+                    });
+                }
+                quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(invalid("unterminated quote"));
+    }
+    if !current.is_empty() {
+        tokens.push(Token {
+            text: current,
+            quoted,
+        });
+    }
+    Ok(tokens)
+}
+
+// None if it isn't a field we know, so it's searched for as a word.
+fn parse_field(text: &str) -> Result<Option<Field>, Error> {
+    let Some((name, value)) = text.split_once(':') else {
+        return Ok(None);
+    };
+    let field = match name.to_lowercase().as_str() {
+        "size" => Field::Size(parse_comparison(name, value, parse_size)?),
+        "seeders" => Field::Seeders(parse_comparison(name, value, |value| value.parse().ok())?),
+        "age" => Field::Age(parse_comparison(name, value, parse_age)?),
+        "files" => Field::Files(parse_comparison(name, value, |value| value.parse().ok())?),
+        "ext" => {
+            let extensions: Vec<String> = value
+                .split(',')
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect();
+            if extensions.is_empty() {
+                return Err(invalid(format!(
+                    "{} needs an extension, like ext:mkv",
+                    name
+                )));
+            }
+            Field::Ext(extensions)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(field))
+}
+
+fn parse_comparison<T>(
+    name: &str,
+    value: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Comparison<T>, Error> {
+    let (op, rest) = [
+        (">=", Op::Ge),
+        ("<=", Op::Le),
+        (">", Op::Gt),
+        ("<", Op::Lt),
+        ("=", Op::Eq),
+    ]
+    .into_iter()
+    .find_map(|(prefix, op)| value.strip_prefix(prefix).map(|rest| (op, rest)))
+    .unwrap_or((Op::Eq, value));
+    let value = parse(rest).ok_or_else(|| invalid(format!("can't read {:?} in {}", rest, name)))?;
+    Ok(Comparison { op, value })
+}
+
+// Like 700MB or 1.5GiB. Plain numbers are bytes.
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, unit) = split_number(value);
+    let multiplier: u64 = match unit.to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" | "k" => 1_000,
+        "mb" | "m" => 1_000_000,
+        "gb" | "g" => 1_000_000_000,
+        "tb" | "t" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return None,
+    };
+    Some((number? * multiplier as f64) as u64)
+}
+
+// Like 12h, 30d, 2w, 6mo or 1y.
+fn parse_age(value: &str) -> Option<Duration> {
+    let (number, unit) = split_number(value);
+    let hours = match unit.to_lowercase().as_str() {
+        "h" => 1.0,
+        "d" => 24.0,
+        "w" => 7.0 * 24.0,
+        "mo" => 30.0 * 24.0,
+        "y" => 365.0 * 24.0,
+        _ => return None,
+    };
+    Some(Duration::seconds((number? * hours * 3600.0) as i64))
+}
+
+fn split_number(value: &str) -> (Option<f64>, &str) {
+    let end = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let number = value[..end]
+        .parse()
+        .ok()
+        .filter(|number: &f64| *number >= 0.0);
+    (number, &value[end..])
+}
+
+// What the filters need from a torrent's info files. It means going over every file, so callers
+// work these out once and keep them.
+#[derive(Clone, Copy, Debug)]
+pub struct FileFacts<'a> {
+    pub num_files: usize,
+    pub extensions: &'a HashSet<String>,
+}
+
+// Every file's extension in lower case, not just the biggest few like file_types.
+pub fn file_extensions(info_files: &InfoFiles) -> HashSet<String> {
+    info_files
+        .upverted_files()
+        .iter()
+        .filter_map(|file| {
+            let ext = Path::new(file.path.last()?.as_str()).extension()?;
+            ext.to_str().map(str::to_lowercase)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{File, InfoHash, SwarmInfo, Timestamp};
+
+    #[test]
+    fn test_parse() {
+        let query: SearchQuery =
+            r#"big "open movie" -trailer -"bad cut" size:>1.5GB -ext:mkv,.MP4 re:zero"#
+                .parse()
+                .unwrap();
+        assert_eq!(query.server_query(), r#"big "open movie" re:zero"#);
+        assert_eq!(query.terms.iter().filter(|term| term.excluded).count(), 2);
+        assert_eq!(
+            query.filters,
+            [
+                Filter {
+                    field: Field::Size(Comparison {
+                        op: Op::Gt,
+                        value: 1_500_000_000
+                    }),
+                    negated: false,
+                },
+                Filter {
+                    field: Field::Ext(vec!["mkv".to_owned(), "mp4".to_owned()]),
+                    negated: true,
+                },
+            ]
+        );
+        assert_eq!("".parse::<SearchQuery>().unwrap(), SearchQuery::default());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for s in [
+            "\"unterminated",
+            "a size:>lots",
+            "a age:<30",
+            "a seeders:>=-1",
+            "a ext:",
+            "size:>1GB",
+            "-only",
+        ] {
+            assert!(s.parse::<SearchQuery>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let now = Utc::now();
+        let item = InfoItem {
+            info_hash: InfoHash::V1([1; 20]),
+            name: "Big Buck Bunny Trailer".to_owned(),
+            size: 2_000_000_000,
+            swarm_info: SwarmInfo {
+                seeders: 12,
+                ..Default::default()
+            },
+            age: Timestamp::new(now - Duration::days(3)),
+            ..Default::default()
+        };
+        let info_files = InfoFiles {
+            files: vec![File {
+                path: Some(vec!["a".into(), "b.MKV".into()]),
+                length: 1,
+            }],
+            ..Default::default()
+        };
+        let extensions = file_extensions(&info_files);
+        let files = FileFacts {
+            num_files: info_files.files().len(),
+            extensions: &extensions,
+        };
+        let matches = |s: &str, files| s.parse::<SearchQuery>().unwrap().matches(&item, files, now);
+        assert_eq!(
+            matches("a size:>1GB seeders:>=10 age:<1w", None),
+            Some(true)
+        );
+        assert_eq!(matches("a age:>1w", None), Some(false));
+        assert_eq!(matches("a -trailer", None), Some(false));
+        assert_eq!(matches("a ext:mkv", None), None);
+        assert_eq!(matches("a ext:mkv", Some(files)), Some(true));
+        assert_eq!(matches("a -ext:mkv", Some(files)), Some(false));
+        assert_eq!(matches("a files:<5", Some(files)), Some(true));
+        // Anything already ruled out doesn't wait on the files.
+        assert_eq!(matches("a seeders:<5 ext:mkv", None), Some(false));
+    }
+}
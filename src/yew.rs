@@ -1,5 +1,5 @@
 use crate::api::*;
-use crate::make_magnet_link;
+use crate::magnet::Magnet;
 use log::info;
 use std::ops::Deref;
 use wasm_bindgen::JsCast;
@@ -36,7 +36,8 @@ fn app() -> Html {
             });
             let state = state.clone();
             wasm_bindgen_futures::spawn_local(async move {
-                let fetched_videos = search(query).await;
+                let indexer = <Indexer>::new(IndexerEndpoint::resolve());
+                let fetched_videos = indexer.search(query, SearchPage::first()).await;
                 let mut app_state = state.deref().clone();
                 app_state.search_result = fetched_videos.ok();
                 state.set(app_state);
@@ -67,9 +68,12 @@ fn torrents_list(TorrentListProps { torrents }: &TorrentListProps) -> Html {
     let rows: Vec<Html> = torrents
         .iter()
         .map(|torrent| {
-            let magnet_link = make_magnet_link(&torrent.info_hash);
+            let magnet_link = Magnet::new(torrent.info_hashes())
+                .with_display_name(torrent.name.clone())
+                .with_exact_length(torrent.size)
+                .to_string();
             html! {
-                <tr key={torrent.info_hash.clone()}>
+                <tr key={torrent.info_hash.to_string()}>
                     <td><a href={magnet_link}>{ torrent.name.clone() }</a></td>
                     <td>{ torrent.swarm_info.seeders }</td>
                 </tr>